@@ -3,78 +3,7 @@ use std::fs;
 use std::path::PathBuf;
 use std::time::SystemTime;
 use tempfile::TempDir;
-
-// Import the functions we want to benchmark from the main crate
-// We'll need to make these functions public or create a lib.rs
-
-#[derive(Debug, Clone)]
-struct FileInfo {
-    name: String,
-    #[allow(dead_code)]
-    path: PathBuf,
-    is_dir: bool,
-    is_hidden: bool,
-    size: u64,
-    modified: Option<SystemTime>,
-}
-
-impl FileInfo {
-    fn from_path(path: PathBuf) -> Result<Self, std::io::Error> {
-        let metadata = fs::metadata(&path)?;
-        let name = path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-
-        let is_hidden = name.starts_with('.');
-        let is_dir = metadata.is_dir();
-        let size = metadata.len();
-        let modified = metadata.modified().ok();
-
-        Ok(FileInfo {
-            name,
-            path,
-            is_dir,
-            is_hidden,
-            size,
-            modified,
-        })
-    }
-}
-
-fn format_size(size: u64, human: bool) -> String {
-    if !human {
-        return size.to_string();
-    }
-
-    const UNITS: &[&str] = &["B", "K", "M", "G", "T"];
-    let mut size = size as f64;
-    let mut unit_index = 0;
-
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
-    }
-
-    if unit_index == 0 {
-        format!("{:.0}{}", size, UNITS[unit_index])
-    } else {
-        format!("{:.1}{}", size, UNITS[unit_index])
-    }
-}
-
-fn format_time(time: Option<SystemTime>) -> String {
-    match time {
-        Some(t) => {
-            let duration = t.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
-            let datetime =
-                chrono::DateTime::from_timestamp(duration.as_secs() as i64, 0).unwrap_or_default();
-            datetime.format("%b %d %H:%M").to_string()
-        }
-        None => "???".to_string(),
-    }
-}
+use zls::{format_size, format_time, sort_entries, Entry, Lister, SortKey, TimeField};
 
 fn create_test_directory(num_files: usize) -> TempDir {
     let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -160,7 +89,7 @@ fn bench_file_info_creation(c: &mut Criterion) {
     group.bench_function("single_file", |b| {
         b.iter(|| {
             if let Some(path) = paths.first() {
-                FileInfo::from_path(black_box(path.clone())).ok()
+                Entry::from_path(black_box(path.clone())).ok()
             } else {
                 None
             }
@@ -171,7 +100,7 @@ fn bench_file_info_creation(c: &mut Criterion) {
         b.iter(|| {
             paths
                 .iter()
-                .map(|p| FileInfo::from_path(black_box(p.clone())))
+                .map(|p| Entry::from_path(black_box(p.clone())))
                 .collect::<Result<Vec<_>, _>>()
                 .ok()
         })
@@ -197,7 +126,7 @@ fn bench_directory_reading(c: &mut Criterion) {
                         .unwrap()
                         .map(|entry| {
                             let entry = entry?;
-                            FileInfo::from_path(entry.path())
+                            Entry::from_path(entry.path())
                         })
                         .collect();
                     entries.ok()
@@ -216,13 +145,19 @@ fn bench_directory_reading(c: &mut Criterion) {
                         .par_bridge()
                         .map(|entry| {
                             let entry = entry?;
-                            FileInfo::from_path(entry.path())
+                            Entry::from_path(entry.path())
                         })
                         .collect();
                     entries.ok()
                 })
             },
         );
+
+        group.bench_with_input(
+            BenchmarkId::new("lister", count),
+            &temp_dir.path(),
+            |b, path| b.iter(|| Lister::new().all(true).list(black_box(path)).ok()),
+        );
     }
 
     group.finish();
@@ -232,18 +167,18 @@ fn bench_sorting(c: &mut Criterion) {
     let mut group = c.benchmark_group("sorting");
 
     let temp_dir = create_test_directory(100);
-    let mut entries: Vec<FileInfo> = fs::read_dir(temp_dir.path())
+    let entries: Vec<Entry> = fs::read_dir(temp_dir.path())
         .expect("Failed to read temp directory")
         .map(|entry| {
             let entry = entry.expect("Failed to get directory entry");
-            FileInfo::from_path(entry.path()).expect("Failed to create FileInfo")
+            Entry::from_path(entry.path()).expect("Failed to create Entry")
         })
         .collect();
 
     group.bench_function("sort_by_name", |b| {
         b.iter(|| {
             let mut entries_copy = black_box(entries.clone());
-            entries_copy.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+            sort_entries(&mut entries_copy, SortKey::Name, false);
             entries_copy
         })
     });
@@ -251,11 +186,11 @@ fn bench_sorting(c: &mut Criterion) {
     group.bench_function("sort_by_time", |b| {
         b.iter(|| {
             let mut entries_copy = black_box(entries.clone());
-            entries_copy.sort_by(|a, b| {
-                b.modified
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .cmp(&a.modified.unwrap_or(SystemTime::UNIX_EPOCH))
-            });
+            sort_entries(
+                &mut entries_copy,
+                SortKey::Time(TimeField::Modification),
+                false,
+            );
             entries_copy
         })
     });
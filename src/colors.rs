@@ -0,0 +1,323 @@
+//! Entry coloring driven by `LS_COLORS`.
+
+use crate::entry::{Entry, FileType};
+use std::{collections::HashMap, env, ffi::OsString};
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color when writing to a terminal, honoring `NO_COLOR`, `CLICOLOR`
+    /// and `CLICOLOR_FORCE`.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Whether output should be colored, given whether it goes to a terminal.
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        self.enabled_with(is_terminal, |key| env::var_os(key))
+    }
+
+    fn enabled_with(self, is_terminal: bool, var: impl Fn(&str) -> Option<OsString>) -> bool {
+        let is_set = |key| var(key).is_some_and(|value| !value.is_empty());
+        let is_set_to_zero = |key| var(key).is_some_and(|value| value == "0");
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            // https://no-color.org and https://bixense.com/clicolors
+            ColorMode::Auto if is_set("NO_COLOR") => false,
+            ColorMode::Auto if is_set("CLICOLOR_FORCE") && !is_set_to_zero("CLICOLOR_FORCE") => {
+                true
+            }
+            ColorMode::Auto if is_set_to_zero("CLICOLOR") => false,
+            ColorMode::Auto => is_terminal,
+        }
+    }
+}
+
+/// Colors for entries, in the format used by GNU `dircolors`.
+///
+/// Each color is an SGR parameter string such as `01;34` or `38;5;208`,
+/// keyed by a two-letter file type code (`di`, `ln`, `ex`, ...) or a
+/// `*suffix` pattern matched against the end of the name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsColors {
+    types: HashMap<String, String>,
+    suffixes: Vec<(String, String)>,
+    /// Color for dotfiles; only used by the built-in theme.
+    hidden: Option<String>,
+}
+
+impl Default for LsColors {
+    /// The built-in zls theme, used when `LS_COLORS` isn't set.
+    fn default() -> Self {
+        let types = [("di", "38;5;12;1"), ("ln", "38;5;14"), ("or", "38;5;9")];
+        LsColors {
+            types: types
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            suffixes: vec![(".rs".to_string(), "38;5;11".to_string())],
+            hidden: Some("38;5;8".to_string()),
+        }
+    }
+}
+
+impl LsColors {
+    /// Reads `LS_COLORS`, falling back to the built-in theme if it is unset.
+    pub fn from_env() -> Self {
+        match env::var("LS_COLORS") {
+            Ok(value) => LsColors::parse(&value),
+            Err(_) => LsColors::default(),
+        }
+    }
+
+    /// Parses a `dircolors` specification like `di=01;34:*.tar=01;31`.
+    ///
+    /// Malformed items, and values that aren't plain SGR parameters, are
+    /// skipped so nothing unexpected is ever written to the terminal.
+    pub fn parse(spec: &str) -> Self {
+        let mut colors = LsColors {
+            types: HashMap::new(),
+            suffixes: Vec::new(),
+            hidden: None,
+        };
+
+        for item in spec.split(':') {
+            let Some((key, value)) = item.split_once('=') else {
+                continue;
+            };
+            if !value.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                continue;
+            }
+            if let Some(suffix) = key.strip_prefix('*') {
+                if !suffix.is_empty() {
+                    colors
+                        .suffixes
+                        .push((suffix.to_string(), value.to_string()));
+                }
+            } else if key.len() == 2 {
+                colors.types.insert(key.to_string(), value.to_string());
+            }
+        }
+        colors
+    }
+
+    /// The SGR parameters to use for `entry`, if it should be colored.
+    pub fn style_for(&self, entry: &Entry) -> Option<&str> {
+        let style = match entry.file_type() {
+            FileType::Symlink if entry.is_broken => self.get("or").or_else(|| self.get("ln")),
+            FileType::Symlink => self.get("ln"),
+            FileType::Directory => {
+                let sticky = entry.mode & 0o1000 != 0;
+                let other_writable = entry.mode & 0o002 != 0;
+                match (sticky, other_writable) {
+                    (true, true) => self.get("tw"),
+                    (false, true) => self.get("ow"),
+                    (true, false) => self.get("st"),
+                    (false, false) => None,
+                }
+                .or_else(|| self.get("di"))
+            }
+            FileType::Fifo => self.get("pi"),
+            FileType::Socket => self.get("so"),
+            FileType::BlockDevice => self.get("bd"),
+            FileType::CharDevice => self.get("cd"),
+            FileType::File => self.file_style(entry),
+        };
+        style
+            .or_else(|| self.get("no"))
+            .filter(|style| !is_reset(style))
+    }
+
+    /// Wraps `text` in the escape sequences for `entry`'s color.
+    pub fn paint(&self, entry: &Entry, text: &str) -> String {
+        match self.style_for(entry) {
+            Some(style) => format!("\x1b[{}m{}\x1b[0m", style, text),
+            None => text.to_string(),
+        }
+    }
+
+    fn file_style(&self, entry: &Entry) -> Option<&str> {
+        let special = if entry.mode & 0o4000 != 0 {
+            self.get("su")
+        } else if entry.mode & 0o2000 != 0 {
+            self.get("sg")
+        } else {
+            None
+        };
+        let executable = || {
+            if entry.mode & 0o111 != 0 {
+                self.get("ex")
+            } else {
+                None
+            }
+        };
+        let multi_link = || {
+            if entry.nlink > 1 {
+                self.get("mh")
+            } else {
+                None
+            }
+        };
+        let hidden = || {
+            if entry.is_hidden {
+                self.hidden.as_deref()
+            } else {
+                None
+            }
+        };
+
+        special
+            .or_else(executable)
+            .or_else(multi_link)
+            .or_else(|| self.suffix_style(&entry.name.to_string_lossy()))
+            .or_else(hidden)
+            .or_else(|| self.get("fi"))
+    }
+
+    /// Matches name suffixes, preferring an exact-case match. Like `ls`, a
+    /// later entry overrides an earlier one for the same suffix.
+    fn suffix_style(&self, name: &str) -> Option<&str> {
+        let exact = self
+            .suffixes
+            .iter()
+            .rfind(|(suffix, _)| name.ends_with(suffix.as_str()));
+        let style = exact.or_else(|| {
+            let name = name.to_lowercase();
+            self.suffixes
+                .iter()
+                .rfind(|(suffix, _)| name.ends_with(&suffix.to_lowercase()))
+        });
+        style.map(|(_, style)| style.as_str())
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.types.get(key).map(String::as_str)
+    }
+}
+
+/// Whether an SGR string only resets attributes, meaning "no color".
+fn is_reset(style: &str) -> bool {
+    style
+        .split(';')
+        .all(|part| part.trim_start_matches('0').is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, mode: u32) -> Entry {
+        Entry {
+            is_dir: mode & 0o170000 == 0o040000,
+            mode,
+            is_symlink: mode & 0o170000 == 0o120000,
+            ..Entry::test_file(name)
+        }
+    }
+
+    #[test]
+    fn test_parse_types_and_suffixes() {
+        let colors = LsColors::parse("di=01;34:ln=01;36:ex=01;32:*.tar=01;31:*README=33");
+
+        assert_eq!(colors.style_for(&entry("src", 0o040755)), Some("01;34"));
+        assert_eq!(colors.style_for(&entry("link", 0o120777)), Some("01;36"));
+        assert_eq!(colors.style_for(&entry("run.sh", 0o100755)), Some("01;32"));
+        assert_eq!(colors.style_for(&entry("a.tar", 0o100644)), Some("01;31"));
+        assert_eq!(colors.style_for(&entry("A.TAR", 0o100644)), Some("01;31"));
+        assert_eq!(colors.style_for(&entry("README", 0o100644)), Some("33"));
+        assert_eq!(colors.style_for(&entry("plain", 0o100644)), None);
+    }
+
+    #[test]
+    fn test_later_suffix_overrides_earlier() {
+        let colors = LsColors::parse("*.gz=31:*.tar.gz=32:*.gz=33");
+
+        assert_eq!(colors.style_for(&entry("a.gz", 0o100644)), Some("33"));
+        assert_eq!(colors.style_for(&entry("a.tar.gz", 0o100644)), Some("33"));
+        let colors = LsColors::parse("*.gz=31:*.tar.gz=32");
+        assert_eq!(colors.style_for(&entry("a.tar.gz", 0o100644)), Some("32"));
+    }
+
+    #[test]
+    fn test_extended_colors_pass_through() {
+        let colors = LsColors::parse("*.rs=38;5;208:*.toml=38;2;255;128;0");
+
+        assert_eq!(
+            colors.paint(&entry("main.rs", 0o100644), "main.rs"),
+            "\x1b[38;5;208mmain.rs\x1b[0m"
+        );
+        assert_eq!(
+            colors.style_for(&entry("Cargo.toml", 0o100644)),
+            Some("38;2;255;128;0")
+        );
+    }
+
+    #[test]
+    fn test_special_types() {
+        let colors = LsColors::parse("or=31:ln=36:tw=30;42:ow=34;42:st=37;44:su=37;41:pi=33");
+
+        let mut broken = entry("dangling", 0o120777);
+        broken.is_broken = true;
+        assert_eq!(colors.style_for(&broken), Some("31"));
+        assert_eq!(colors.style_for(&entry("tmp", 0o041777)), Some("30;42"));
+        assert_eq!(colors.style_for(&entry("shared", 0o040777)), Some("34;42"));
+        assert_eq!(colors.style_for(&entry("sticky", 0o041755)), Some("37;44"));
+        assert_eq!(colors.style_for(&entry("sudo", 0o104755)), Some("37;41"));
+        assert_eq!(colors.style_for(&entry("fifo", 0o010644)), Some("33"));
+    }
+
+    #[test]
+    fn test_executable_beats_suffix() {
+        let colors = LsColors::parse("ex=32:*.sh=33");
+        assert_eq!(colors.style_for(&entry("run.sh", 0o100755)), Some("32"));
+        assert_eq!(colors.style_for(&entry("lib.sh", 0o100644)), Some("33"));
+    }
+
+    #[test]
+    fn test_rejects_non_sgr_values() {
+        let colors = LsColors::parse("di=\x1b]0;pwned\x07:fi=00:ln");
+        assert_eq!(colors.style_for(&entry("src", 0o040755)), None);
+        assert_eq!(colors.style_for(&entry("plain", 0o100644)), None);
+    }
+
+    #[test]
+    fn test_color_mode() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+
+        assert!(ColorMode::Auto.enabled_with(true, env(&[])));
+        assert!(!ColorMode::Auto.enabled_with(false, env(&[])));
+        assert!(!ColorMode::Auto.enabled_with(true, env(&[("NO_COLOR", "1")])));
+        assert!(ColorMode::Auto.enabled_with(true, env(&[("NO_COLOR", "")])));
+        assert!(ColorMode::Auto.enabled_with(false, env(&[("CLICOLOR_FORCE", "1")])));
+        assert!(!ColorMode::Auto.enabled_with(false, env(&[("CLICOLOR_FORCE", "0")])));
+        assert!(!ColorMode::Auto.enabled_with(true, env(&[("CLICOLOR", "0")])));
+        assert!(!ColorMode::Auto
+            .enabled_with(false, env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])));
+
+        // Explicit choices win over the environment
+        assert!(ColorMode::Always.enabled_with(false, env(&[("NO_COLOR", "1")])));
+        assert!(!ColorMode::Never.enabled_with(true, env(&[("CLICOLOR_FORCE", "1")])));
+    }
+
+    #[test]
+    fn test_default_theme() {
+        let colors = LsColors::default();
+        assert_eq!(colors.style_for(&entry("src", 0o040755)), Some("38;5;12;1"));
+        assert_eq!(
+            colors.style_for(&entry("main.rs", 0o100644)),
+            Some("38;5;11")
+        );
+        assert_eq!(colors.style_for(&entry(".env", 0o100644)), Some("38;5;8"));
+        assert_eq!(colors.style_for(&entry("plain", 0o100644)), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! CSV and TSV output.
+
+use crate::{
+    entry::Entry,
+    format::{format_mode, format_rfc3339},
+    owner::{group_name, user_name},
+    quote::hide_control_chars,
+};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+    time::SystemTime,
+};
+
+/// A column that can be selected for CSV and TSV output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Name,
+    Path,
+    Type,
+    Size,
+    Blocks,
+    Permissions,
+    Mode,
+    Links,
+    Uid,
+    Gid,
+    User,
+    Group,
+    Mtime,
+    Atime,
+    Ctime,
+    Btime,
+    Target,
+}
+
+impl Column {
+    /// The columns written when none are selected.
+    pub const DEFAULT: &'static [Column] = &[
+        Column::Name,
+        Column::Path,
+        Column::Type,
+        Column::Size,
+        Column::Permissions,
+        Column::User,
+        Column::Group,
+        Column::Mtime,
+    ];
+
+    const ALL: &'static [Column] = &[
+        Column::Name,
+        Column::Path,
+        Column::Type,
+        Column::Size,
+        Column::Blocks,
+        Column::Permissions,
+        Column::Mode,
+        Column::Links,
+        Column::Uid,
+        Column::Gid,
+        Column::User,
+        Column::Group,
+        Column::Mtime,
+        Column::Atime,
+        Column::Ctime,
+        Column::Btime,
+        Column::Target,
+    ];
+
+    /// The name used for the column in `--columns` and the header row.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Path => "path",
+            Column::Type => "type",
+            Column::Size => "size",
+            Column::Blocks => "blocks",
+            Column::Permissions => "permissions",
+            Column::Mode => "mode",
+            Column::Links => "links",
+            Column::Uid => "uid",
+            Column::Gid => "gid",
+            Column::User => "user",
+            Column::Group => "group",
+            Column::Mtime => "mtime",
+            Column::Atime => "atime",
+            Column::Ctime => "ctime",
+            Column::Btime => "btime",
+            Column::Target => "target",
+        }
+    }
+
+    fn value(self, entry: &Entry) -> Cow<'_, str> {
+        match self {
+            Column::Name => entry.name.to_string_lossy(),
+            Column::Path => entry.path.to_string_lossy(),
+            Column::Type => Cow::Borrowed(entry.file_type().as_str()),
+            Column::Size => Cow::Owned(entry.size.to_string()),
+            Column::Blocks => Cow::Owned(entry.blocks.to_string()),
+            Column::Permissions => Cow::Owned(format_mode(entry.mode)),
+            Column::Mode => Cow::Owned(format!("{:o}", entry.mode & 0o7777)),
+            Column::Links => Cow::Owned(entry.nlink.to_string()),
+            Column::Uid => Cow::Owned(entry.uid.to_string()),
+            Column::Gid => Cow::Owned(entry.gid.to_string()),
+            Column::User => Cow::Owned(user_name(entry.uid)),
+            Column::Group => Cow::Owned(group_name(entry.gid)),
+            Column::Mtime => time_value(entry.modified),
+            Column::Atime => time_value(entry.accessed),
+            Column::Ctime => time_value(entry.changed),
+            Column::Btime => time_value(entry.created),
+            Column::Target => entry
+                .link_target
+                .as_ref()
+                .map(|target| target.to_string_lossy())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Column::ALL
+            .iter()
+            .copied()
+            .find(|column| column.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Column::ALL.iter().map(|column| column.as_str()).collect();
+                format!(
+                    "unknown column '{}' (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// An RFC 3339 timestamp, or an empty value when it's unknown or can't be
+/// represented.
+fn time_value(time: Option<SystemTime>) -> Cow<'static, str> {
+    Cow::Owned(time.and_then(format_rfc3339).unwrap_or_default())
+}
+
+/// Writes a header row followed by one row per entry, quoted per RFC 4180.
+///
+/// With `hide_control` set, characters that could affect a terminal are
+/// replaced with `?` first.
+pub(crate) fn write_csv<'a, W, I>(
+    out: &mut W,
+    entries: I,
+    columns: &[Column],
+    hide_control: bool,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Entry>,
+{
+    write_rows(out, entries, columns, hide_control, ",", "\r\n", quote_csv)
+}
+
+/// Writes a header row followed by one row per entry, separated by tabs.
+///
+/// Tabs, newlines and backslashes in values are escaped as `\t`, `\n`, `\r`
+/// and `\\` so each entry stays on one line. `hide_control` works as in
+/// [`write_csv`].
+pub(crate) fn write_tsv<'a, W, I>(
+    out: &mut W,
+    entries: I,
+    columns: &[Column],
+    hide_control: bool,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Entry>,
+{
+    write_rows(out, entries, columns, hide_control, "\t", "\n", escape_tsv)
+}
+
+fn write_rows<'a, W, I>(
+    out: &mut W,
+    entries: I,
+    columns: &[Column],
+    hide_control: bool,
+    separator: &str,
+    terminator: &str,
+    escape: fn(&str) -> Cow<'_, str>,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Entry>,
+{
+    let header: Vec<&str> = columns.iter().map(|column| column.as_str()).collect();
+    write!(out, "{}{}", header.join(separator), terminator)?;
+
+    for entry in entries {
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                out.write_all(separator.as_bytes())?;
+            }
+            let raw = column.value(entry);
+            let value = if hide_control {
+                hide_control_chars(&raw)
+            } else {
+                Cow::Borrowed(raw.as_ref())
+            };
+            out.write_all(escape(&value).as_bytes())?;
+        }
+        out.write_all(terminator.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn quote_csv(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn escape_tsv(value: &str) -> Cow<'_, str> {
+    if !value.contains(['\t', '\n', '\r', '\\']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn entry(name: &str) -> Entry {
+        Entry {
+            size: 42,
+            ..Entry::test_file(name)
+        }
+    }
+
+    #[test]
+    fn test_csv_quotes_special_names() {
+        let entries = [
+            entry("plain.txt"),
+            entry("a,b"),
+            entry("say \"hi\""),
+            entry("multi\nline"),
+        ];
+        let mut out = Vec::new();
+        write_csv(&mut out, &entries, &[Column::Name, Column::Size], false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,size\r\n\
+             plain.txt,42\r\n\
+             \"a,b\",42\r\n\
+             \"say \"\"hi\"\"\",42\r\n\
+             \"multi\nline\",42\r\n"
+        );
+    }
+
+    #[test]
+    fn test_tsv_escapes_separators() {
+        let entries = [entry("tab\there"), entry("back\\slash")];
+        let mut out = Vec::new();
+        write_tsv(&mut out, &entries, &[Column::Name, Column::Type], false).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name\ttype\ntab\\there\tfile\nback\\\\slash\tfile\n"
+        );
+    }
+
+    #[test]
+    fn test_hides_control_chars() {
+        let entries = [entry("a\x1b[2Jb\nc")];
+        let mut out = Vec::new();
+        write_csv(&mut out, &entries, &[Column::Name], true).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "name\r\na?[2Jb?c\r\n");
+
+        let mut out = Vec::new();
+        write_tsv(&mut out, &entries, &[Column::Name], true).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "name\na?[2Jb?c\n");
+    }
+
+    #[test]
+    fn test_out_of_range_time_is_empty() {
+        let mut far = entry("far");
+        far.modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(99_999_999_999_999));
+        let mut out = Vec::new();
+        write_csv(&mut out, &[far], &[Column::Name, Column::Mtime], false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "name,mtime\r\nfar,\r\n");
+    }
+
+    #[test]
+    fn test_column_from_str() {
+        assert_eq!("mtime".parse::<Column>(), Ok(Column::Mtime));
+        assert!("bogus"
+            .parse::<Column>()
+            .unwrap_err()
+            .contains("name, path"));
+    }
+}
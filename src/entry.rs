@@ -0,0 +1,392 @@
+use std::{ffi::OsString, fs, io, path::PathBuf, time::SystemTime};
+
+/// The kind of file an [`Entry`] describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileType {
+    /// A lowercase name for the type, as used in machine-readable output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileType::File => "file",
+            FileType::Directory => "directory",
+            FileType::Symlink => "symlink",
+            FileType::Fifo => "fifo",
+            FileType::Socket => "socket",
+            FileType::BlockDevice => "block_device",
+            FileType::CharDevice => "char_device",
+        }
+    }
+}
+
+/// Which of a file's timestamps to show and sort by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TimeField {
+    /// When the contents last changed (`mtime`).
+    #[default]
+    Modification,
+    /// When the file was last read (`atime`).
+    Access,
+    /// When the contents or metadata last changed (`ctime`).
+    Change,
+    /// When the file was created, where the platform and filesystem record it.
+    Birth,
+}
+
+/// A single directory entry along with the metadata zls displays.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// The file name, exactly as the filesystem stores it.
+    pub name: OsString,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub is_hidden: bool,
+    /// File type and permission bits, as in `st_mode`.
+    pub mode: u32,
+    pub nlink: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// Space allocated on disk, in 512-byte blocks as in `st_blocks`.
+    pub blocks: u64,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// When the status last changed; only known on unix.
+    pub changed: Option<SystemTime>,
+    /// When the file was created, if the filesystem records it.
+    pub created: Option<SystemTime>,
+    /// Whether the path itself is a symbolic link.
+    pub is_symlink: bool,
+    /// Where the link points, unless the entry was dereferenced.
+    pub link_target: Option<PathBuf>,
+    /// Whether the link points at something that doesn't exist.
+    pub is_broken: bool,
+}
+
+impl Entry {
+    /// Reads the metadata for `path` and builds an entry from it.
+    ///
+    /// Symlinks are not followed, so a link is described by its own metadata
+    /// and its target is recorded in `link_target`.
+    pub fn from_path(path: PathBuf) -> io::Result<Self> {
+        Self::read(path, false)
+    }
+
+    /// Like [`Entry::from_path`], but describes what a symlink points to.
+    ///
+    /// Broken links can't be followed and fall back to the link itself.
+    pub fn from_path_dereferenced(path: PathBuf) -> io::Result<Self> {
+        Self::read(path, true)
+    }
+
+    /// The timestamp selected by `field`, or `None` if it's unknown.
+    pub fn time(&self, field: TimeField) -> Option<SystemTime> {
+        match field {
+            TimeField::Modification => self.modified,
+            TimeField::Access => self.accessed,
+            TimeField::Change => self.changed,
+            TimeField::Birth => self.created,
+        }
+    }
+
+    /// The type of the file, taken from the mode bits.
+    pub fn file_type(&self) -> FileType {
+        match self.mode & 0o170000 {
+            0o040000 => FileType::Directory,
+            0o120000 => FileType::Symlink,
+            0o010000 => FileType::Fifo,
+            0o140000 => FileType::Socket,
+            0o060000 => FileType::BlockDevice,
+            0o020000 => FileType::CharDevice,
+            _ if self.is_dir => FileType::Directory,
+            _ => FileType::File,
+        }
+    }
+
+    fn read(path: PathBuf, dereference: bool) -> io::Result<Self> {
+        let link_metadata = fs::symlink_metadata(&path)?;
+        let is_symlink = link_metadata.file_type().is_symlink();
+        let target_metadata = if is_symlink {
+            fs::metadata(&path).ok()
+        } else {
+            None
+        };
+        let is_broken = is_symlink && target_metadata.is_none();
+
+        let (metadata, link_target) = match target_metadata {
+            Some(metadata) if dereference => (metadata, None),
+            _ if is_symlink => (link_metadata, fs::read_link(&path).ok()),
+            _ => (link_metadata, None),
+        };
+
+        let name = path.file_name().unwrap_or_default().to_os_string();
+
+        let is_hidden = name.as_encoded_bytes().starts_with(b".");
+        let is_dir = metadata.is_dir();
+        let size = metadata.len();
+        let modified = metadata.modified().ok();
+        let accessed = metadata.accessed().ok();
+        // Uses statx on Linux, failing where the filesystem has no birth time
+        let created = metadata.created().ok();
+
+        #[cfg(unix)]
+        let (mode, nlink, uid, gid, changed, blocks) = {
+            use std::os::unix::fs::MetadataExt;
+            (
+                metadata.mode(),
+                metadata.nlink(),
+                metadata.uid(),
+                metadata.gid(),
+                unix_time(metadata.ctime(), metadata.ctime_nsec()),
+                metadata.blocks(),
+            )
+        };
+        #[cfg(not(unix))]
+        let (mode, nlink, uid, gid, changed, blocks) =
+            (synthetic_mode(&metadata), 1, 0, 0, None, size.div_ceil(512));
+
+        Ok(Entry {
+            name,
+            path,
+            is_dir,
+            is_hidden,
+            mode,
+            nlink,
+            uid,
+            gid,
+            size,
+            blocks,
+            modified,
+            accessed,
+            changed,
+            created,
+            is_symlink,
+            link_target,
+            is_broken,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Entry {
+    /// A regular file at `path` with empty metadata, for tests to adjust with
+    /// struct update syntax.
+    pub(crate) fn test_file(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let name = path.file_name().unwrap_or_default().to_os_string();
+        Entry {
+            is_hidden: name.as_encoded_bytes().starts_with(b"."),
+            name,
+            path,
+            is_dir: false,
+            mode: 0o100644,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            blocks: 0,
+            modified: None,
+            accessed: None,
+            changed: None,
+            created: None,
+            is_symlink: false,
+            link_target: None,
+            is_broken: false,
+        }
+    }
+
+    /// Like [`Entry::test_file`], but a directory.
+    pub(crate) fn test_dir(path: &str) -> Self {
+        Entry {
+            is_dir: true,
+            mode: 0o040755,
+            nlink: 2,
+            size: 4096,
+            ..Entry::test_file(path)
+        }
+    }
+}
+
+/// Converts seconds and nanoseconds since the epoch, as in `struct stat`.
+#[cfg(unix)]
+fn unix_time(secs: i64, nsecs: i64) -> Option<SystemTime> {
+    use std::time::Duration;
+
+    let nanos = Duration::from_nanos(u64::try_from(nsecs).ok()?);
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs.unsigned_abs()) + nanos)
+    } else {
+        SystemTime::UNIX_EPOCH
+            .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+            .checked_add(nanos)
+    }
+}
+
+/// Approximates unix mode bits on platforms that don't have them.
+#[cfg(not(unix))]
+fn synthetic_mode(metadata: &fs::Metadata) -> u32 {
+    let mode = if metadata.is_dir() {
+        0o040755
+    } else {
+        0o100644
+    };
+    if metadata.permissions().readonly() {
+        mode & !0o222
+    } else {
+        mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_entry_hidden_detection() {
+        let entry = Entry::test_file(".hidden_file");
+
+        assert!(entry.is_hidden);
+        assert_eq!(entry.name, ".hidden_file");
+    }
+
+    #[test]
+    fn test_entry_regular_file() {
+        let entry = Entry {
+            size: 1024,
+            modified: Some(SystemTime::now()),
+            ..Entry::test_file("regular_file.txt")
+        };
+
+        assert!(!entry.is_hidden);
+        assert!(!entry.is_dir);
+        assert_eq!(entry.size, 1024);
+    }
+
+    #[test]
+    fn test_entry_directory() {
+        let entry = Entry::test_dir("directory");
+
+        assert!(!entry.is_hidden);
+        assert!(entry.is_dir);
+        assert_eq!(entry.file_type(), FileType::Directory);
+    }
+
+    #[test]
+    fn test_entry_from_path() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(".config");
+        fs::write(&path, "abc").unwrap();
+
+        let entry = Entry::from_path(path).unwrap();
+        assert_eq!(entry.name, ".config");
+        assert!(entry.is_hidden);
+        assert!(!entry.is_dir);
+        assert_eq!(entry.size, 3);
+        assert!(entry.modified.is_some());
+        assert!(entry.accessed.is_some());
+        assert_eq!(entry.time(TimeField::Modification), entry.modified);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_entry_change_time() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+
+        let entry = Entry::from_path(path).unwrap();
+        let changed = entry.time(TimeField::Change).unwrap();
+        let secs = changed
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert_eq!(secs as i64, metadata.ctime());
+        assert_eq!(
+            unix_time(-1, 500_000_000),
+            SystemTime::UNIX_EPOCH.checked_sub(std::time::Duration::from_millis(500))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_entry_non_utf8_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        let name = std::ffi::OsStr::from_bytes(b".caf\xe9");
+        // Some filesystems only accept UTF-8 names
+        if fs::write(dir.path().join(name), "").is_err() {
+            return;
+        }
+
+        let entry = Entry::from_path(dir.path().join(name)).unwrap();
+        assert_eq!(entry.name, name);
+        assert!(entry.is_hidden);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_entry_unix_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("script.sh");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        fs::hard_link(&path, dir.path().join("link")).unwrap();
+
+        let entry = Entry::from_path(path).unwrap();
+        assert_eq!(entry.mode & 0o7777, 0o750);
+        assert_eq!(entry.nlink, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_entry_symlink_not_followed() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink("target", &link).unwrap();
+
+        let entry = Entry::from_path(link.clone()).unwrap();
+        assert!(entry.is_symlink);
+        assert!(!entry.is_dir);
+        assert!(!entry.is_broken);
+        assert_eq!(entry.mode & 0o170000, 0o120000);
+        assert_eq!(entry.link_target, Some(PathBuf::from("target")));
+        assert_eq!(entry.file_type(), FileType::Symlink);
+
+        let entry = Entry::from_path_dereferenced(link).unwrap();
+        assert!(entry.is_symlink);
+        assert!(entry.is_dir);
+        assert_eq!(entry.link_target, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_entry_broken_symlink() {
+        let dir = tempdir().unwrap();
+        let link = dir.path().join("dangling");
+        std::os::unix::fs::symlink("missing", &link).unwrap();
+
+        let entry = Entry::from_path(link.clone()).unwrap();
+        assert!(entry.is_broken);
+        assert_eq!(entry.link_target, Some(PathBuf::from("missing")));
+
+        let entry = Entry::from_path_dereferenced(link).unwrap();
+        assert!(entry.is_broken);
+        assert_eq!(entry.link_target, Some(PathBuf::from("missing")));
+    }
+}
@@ -0,0 +1,87 @@
+use std::{fmt, io, path::PathBuf};
+
+/// What zls was doing when a [`ListError`] occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorContext {
+    /// Reading the metadata of a file.
+    Access,
+    /// Reading the contents of a directory.
+    OpenDirectory,
+}
+
+/// A file or directory that couldn't be read while listing.
+///
+/// Displays like GNU `ls`, e.g. `cannot access 'x': Permission denied`.
+#[derive(Debug)]
+pub struct ListError {
+    pub path: PathBuf,
+    pub context: ErrorContext,
+    pub source: io::Error,
+}
+
+impl ListError {
+    pub fn access(path: PathBuf, source: io::Error) -> Self {
+        ListError {
+            path,
+            context: ErrorContext::Access,
+            source,
+        }
+    }
+
+    pub fn open_directory(path: PathBuf, source: io::Error) -> Self {
+        ListError {
+            path,
+            context: ErrorContext::OpenDirectory,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for ListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.context {
+            ErrorContext::Access => "cannot access",
+            ErrorContext::OpenDirectory => "cannot open directory",
+        };
+        // io::Error appends " (os error N)", which ls doesn't show
+        let message = self.source.to_string();
+        let message = message
+            .split(" (os error")
+            .next()
+            .unwrap_or(message.as_str());
+        write!(f, "{} '{}': {}", action, self.path.display(), message)
+    }
+}
+
+impl std::error::Error for ListError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_matches_ls() {
+        let error = ListError::access(
+            PathBuf::from("secret"),
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        assert_eq!(
+            error.to_string(),
+            "cannot access 'secret': permission denied"
+        );
+
+        #[cfg(unix)]
+        {
+            let error =
+                ListError::open_directory(PathBuf::from("dir"), io::Error::from_raw_os_error(13));
+            assert_eq!(
+                error.to_string(),
+                "cannot open directory 'dir': Permission denied"
+            );
+        }
+    }
+}
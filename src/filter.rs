@@ -0,0 +1,346 @@
+//! Choosing which entries to list by name, type, size and age.
+
+use crate::{
+    entry::{Entry, FileType, TimeField},
+    query::Query,
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::bytes::{Regex, RegexBuilder};
+use std::{ffi::OsStr, path::Path, time::SystemTime};
+
+/// A glob or regular expression matched against entry names.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// A glob, matched against the name, or against the path relative to the
+    /// listed directory when it contains a `/`.
+    Glob {
+        matcher: GlobMatcher,
+        match_path: bool,
+    },
+    /// A regular expression, searched for in the path relative to the listed
+    /// directory, which is just the name outside recursive listings.
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Parses a shell glob such as `*.rs` or `src/**/*.toml`.
+    ///
+    /// `*` and `?` don't match `/`, while `**` matches any number of
+    /// directories.
+    pub fn glob(glob: &str, case_insensitive: bool) -> Result<Self, String> {
+        let matcher = GlobBuilder::new(glob)
+            .case_insensitive(case_insensitive)
+            .literal_separator(true)
+            .build()
+            .map_err(|error| error.kind().to_string())?
+            .compile_matcher();
+        Ok(Pattern::Glob {
+            matcher,
+            match_path: glob.contains('/'),
+        })
+    }
+
+    /// Parses a regular expression in the syntax of the `regex` crate.
+    pub fn regex(pattern: &str, case_insensitive: bool) -> Result<Self, String> {
+        RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map(Pattern::Regex)
+            .map_err(|error| error.to_string())
+    }
+
+    fn is_match(&self, name: &OsStr, relative: &Path) -> bool {
+        match self {
+            Pattern::Glob {
+                matcher,
+                match_path: true,
+            } => matcher.is_match(relative),
+            Pattern::Glob { matcher, .. } => matcher.is_match(name),
+            Pattern::Regex(regex) => regex.is_match(relative.as_os_str().as_encoded_bytes()),
+        }
+    }
+}
+
+/// Which entries a listing keeps.
+///
+/// An entry is kept unless it matches one of the excluded patterns, and, if
+/// any patterns are included, only when it matches one of those. The type,
+/// size, age and query conditions apply to every entry, and all of them must
+/// hold.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    file_types: Vec<FileType>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+    time_field: TimeField,
+    queries: Vec<Query>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only entries that match `pattern` or another included pattern.
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    /// Hide entries that match `pattern`. Excluded directories aren't
+    /// descended into.
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    /// Keep only entries of type `file_type` or another given type.
+    pub fn file_type(mut self, file_type: FileType) -> Self {
+        self.file_types.push(file_type);
+        self
+    }
+
+    /// Keep only entries of at least `size` bytes.
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    /// Keep only entries of at most `size` bytes.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Keep only entries whose time is after `time`.
+    pub fn newer_than(mut self, time: SystemTime) -> Self {
+        self.newer_than = Some(time);
+        self
+    }
+
+    /// Keep only entries whose time is before `time`.
+    pub fn older_than(mut self, time: SystemTime) -> Self {
+        self.older_than = Some(time);
+        self
+    }
+
+    /// Set which timestamp [`Filter::newer_than`] and [`Filter::older_than`]
+    /// compare, the modification time by default. Entries without that
+    /// timestamp never match an age condition.
+    pub fn time_field(mut self, field: TimeField) -> Self {
+        self.time_field = field;
+        self
+    }
+
+    /// Keep only entries that match `query` and every other given query.
+    pub fn query(mut self, query: Query) -> Self {
+        self.queries.push(query);
+        self
+    }
+
+    /// Whether the filter keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty()
+            && self.exclude.is_empty()
+            && self.file_types.is_empty()
+            && self.min_size.is_none()
+            && self.max_size.is_none()
+            && self.newer_than.is_none()
+            && self.older_than.is_none()
+            && self.queries.is_empty()
+    }
+
+    /// Whether `entry`, found at `relative` below the listed directory, is
+    /// kept.
+    pub fn keeps(&self, entry: &Entry, relative: &Path) -> bool {
+        !self.excludes(&entry.name, relative) && self.matches(entry, relative)
+    }
+
+    /// Whether `entry` meets every condition other than the excluded
+    /// patterns.
+    ///
+    /// Unlike an excluded directory, a directory that doesn't match can still
+    /// have entries below it that do.
+    pub(crate) fn matches(&self, entry: &Entry, relative: &Path) -> bool {
+        let time = entry.time(self.time_field);
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| pattern.is_match(&entry.name, relative)))
+            && (self.file_types.is_empty() || self.file_types.contains(&entry.file_type()))
+            && self.min_size.is_none_or(|min| entry.size >= min)
+            && self.max_size.is_none_or(|max| entry.size <= max)
+            && self
+                .newer_than
+                .is_none_or(|bound| time.is_some_and(|time| time > bound))
+            && self
+                .older_than
+                .is_none_or(|bound| time.is_some_and(|time| time < bound))
+            && self
+                .queries
+                .iter()
+                .all(|query| query.matches(entry, relative))
+    }
+
+    /// Whether the entry `name` at `relative` matches an excluded pattern.
+    pub(crate) fn excludes(&self, name: &OsStr, relative: &Path) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.is_match(name, relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, is_dir: bool) -> Entry {
+        if is_dir {
+            Entry::test_dir(path)
+        } else {
+            Entry::test_file(path)
+        }
+    }
+
+    fn kept(filter: &Filter, paths: &[(&str, bool)]) -> Vec<String> {
+        paths
+            .iter()
+            .filter(|(path, is_dir)| filter.keeps(&entry(path, *is_dir), Path::new(path)))
+            .map(|(path, _)| path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_include_globs() {
+        let filter = Filter::new()
+            .include(Pattern::glob("*.rs", false).unwrap())
+            .include(Pattern::glob("*.toml", false).unwrap());
+        assert_eq!(
+            kept(
+                &filter,
+                &[
+                    ("Cargo.toml", false),
+                    ("README.md", false),
+                    ("src", true),
+                    ("src/main.rs", false),
+                    ("src/MAIN.RS", false),
+                ]
+            ),
+            ["Cargo.toml", "src/main.rs"]
+        );
+
+        let filter = Filter::new().include(Pattern::glob("*.rs", true).unwrap());
+        assert_eq!(kept(&filter, &[("src/MAIN.RS", false)]), ["src/MAIN.RS"]);
+    }
+
+    #[test]
+    fn test_globs_with_slash_match_relative_paths() {
+        let filter = Filter::new().include(Pattern::glob("src/*.rs", false).unwrap());
+        assert_eq!(
+            kept(
+                &filter,
+                &[
+                    ("main.rs", false),
+                    ("src/main.rs", false),
+                    ("src/bin/zls.rs", false)
+                ]
+            ),
+            ["src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_exclude_applies_to_directories() {
+        let filter = Filter::new()
+            .exclude(Pattern::glob("target", false).unwrap())
+            .exclude(Pattern::glob("*.LOG", true).unwrap());
+        assert_eq!(
+            kept(
+                &filter,
+                &[
+                    ("target", true),
+                    ("src", true),
+                    ("a.log", false),
+                    ("a.rs", false)
+                ]
+            ),
+            ["src", "a.rs"]
+        );
+    }
+
+    #[test]
+    fn test_regex_matches_relative_path() {
+        let filter = Filter::new().include(Pattern::regex(r"^src/.*_test\.rs$", false).unwrap());
+        assert_eq!(
+            kept(
+                &filter,
+                &[
+                    ("a_test.rs", false),
+                    ("src/a_test.rs", false),
+                    ("src/a.rs", false)
+                ]
+            ),
+            ["src/a_test.rs"]
+        );
+
+        let filter = Filter::new().include(Pattern::regex("readme", true).unwrap());
+        assert_eq!(kept(&filter, &[("README.md", false)]), ["README.md"]);
+    }
+
+    #[test]
+    fn test_type_size_and_age() {
+        let now = SystemTime::now();
+        let day = std::time::Duration::from_secs(86400);
+        let mut old_big = entry("old_big", false);
+        old_big.size = 20 << 20;
+        old_big.modified = Some(now - 30 * day);
+        let mut new_big = entry("new_big", false);
+        new_big.size = 20 << 20;
+        new_big.modified = Some(now);
+        let mut small = entry("small", false);
+        small.modified = Some(now - 30 * day);
+        let unknown_time = entry("unknown_time", false);
+        let dir = entry("dir", true);
+        let entries = [old_big, new_big, small, unknown_time, dir];
+        let kept = |filter: &Filter| -> Vec<String> {
+            entries
+                .iter()
+                .filter(|entry| filter.keeps(entry, Path::new(&entry.name)))
+                .map(|entry| entry.name.to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let filter = Filter::new()
+            .file_type(FileType::File)
+            .min_size(10 << 20)
+            .older_than(now - 7 * day);
+        assert_eq!(kept(&filter), ["old_big"]);
+
+        let filter = Filter::new().max_size(0).newer_than(now - 60 * day);
+        assert_eq!(kept(&filter), ["small"]);
+
+        let filter = Filter::new()
+            .file_type(FileType::Directory)
+            .file_type(FileType::Symlink);
+        assert_eq!(kept(&filter), ["dir"]);
+
+        // Only access times are considered, and none are known
+        let filter = Filter::new()
+            .newer_than(now - 60 * day)
+            .time_field(TimeField::Access);
+        assert!(kept(&filter).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(Pattern::glob("a[", false).is_err());
+        assert!(Pattern::regex("(", false)
+            .unwrap_err()
+            .contains("unclosed group"));
+    }
+}
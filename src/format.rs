@@ -0,0 +1,526 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc,
+};
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+/// How long ago a time can be and still count as recent, like `ls`: half of
+/// an average Gregorian year.
+const SIX_MONTHS: Duration = Duration::from_secs(31_556_952 / 2);
+
+/// Formats a byte count, using B/K/M/G/T units when `human` is set.
+pub fn format_size(size: u64, human: bool) -> String {
+    if !human {
+        return size.to_string();
+    }
+
+    const UNITS: &[&str] = &["B", "K", "M", "G", "T"];
+    let mut size = size as f64;
+    let mut unit_index = 0;
+
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    if unit_index == 0 {
+        format!("{:.0}{}", size, UNITS[unit_index])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit_index])
+    }
+}
+
+/// Parses a size such as `512`, `10M` or `1.5G`, using the units
+/// [`format_size`] prints.
+///
+/// Units are powers of 1024 and may be lowercase or followed by `B` or `iB`,
+/// so `10M`, `10m`, `10MB` and `10MiB` are the same size.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "invalid size '{}' (expected a number such as 512, 10M or 1.5G)",
+            s
+        )
+    };
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let unit = unit.to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let power = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => return Err(invalid()),
+    };
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let size = number * 1024f64.powi(power);
+    if size.is_finite() && size <= u64::MAX as f64 {
+        Ok(size.round() as u64)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Parses a point in time given as an age before `now`, such as `3d` or
+/// `2w`, or as a date such as `2024-01-31` or `2024-01-31 12:00` in the local
+/// time zone.
+///
+/// Ages are a whole number followed by `s`, `m`, `h`, `d`, `w` or `y` (365
+/// days), or the unit spelled out. Dates may also be RFC 3339 timestamps with
+/// an offset.
+pub fn parse_time(s: &str, now: SystemTime) -> Result<SystemTime, String> {
+    parse_time_in(s, now, &Local)
+}
+
+fn parse_time_in<Tz: TimeZone>(s: &str, now: SystemTime, zone: &Tz) -> Result<SystemTime, String> {
+    if let Some(age) = parse_age(s) {
+        return now
+            .checked_sub(age)
+            .ok_or_else(|| format!("age '{}' is too long", s));
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.into());
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    });
+    naive
+        .and_then(|naive| zone.from_local_datetime(&naive).earliest())
+        .map(SystemTime::from)
+        .ok_or_else(|| {
+            format!(
+                "invalid time '{}' (expected an age such as 3d or 2w, or a date such as 2024-01-31)",
+                s
+            )
+        })
+}
+
+/// Parses an age such as `3d` or `90 minutes`, as accepted by [`parse_time`].
+pub(crate) fn parse_age(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = s.split_at(split);
+    let unit_secs = match unit.trim_start() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        "y" | "year" | "years" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let secs = number.parse::<u64>().ok()?.checked_mul(unit_secs)?;
+    Some(Duration::from_secs(secs))
+}
+
+/// Formats mode bits as an `ls -l` style string such as `drwxr-xr-x`.
+pub fn format_mode(mode: u32) -> String {
+    let type_char = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        _ => '-',
+    };
+
+    let mut result = String::with_capacity(10);
+    result.push(type_char);
+    for (shift, special_bit, special_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')]
+    {
+        let bits = (mode >> shift) & 0o7;
+        result.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        result.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        result.push(match (mode & special_bit != 0, bits & 0o1 != 0) {
+            (true, true) => special_char,
+            (true, false) => special_char.to_ascii_uppercase(),
+            (false, true) => 'x',
+            (false, false) => '-',
+        });
+    }
+    result
+}
+
+/// How timestamps are shown in the human-readable layouts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// `Jan  5 13:45` for the last six months and `Jan  5  2023` otherwise,
+    /// like `ls`.
+    #[default]
+    Standard,
+    /// `2024-01-05 13:45:12.123456789 +0100`.
+    FullIso,
+    /// `2024-01-05 13:45`.
+    LongIso,
+    /// `01-05 13:45` for the last six months and `2023-01-05 ` otherwise.
+    Iso,
+    /// How long ago, such as `3 hours ago`.
+    Relative,
+    /// `strftime` formats for older and for recent times.
+    Custom { old: String, recent: String },
+}
+
+impl TimeStyle {
+    /// Formats `time` in the local time zone, or the one `TZ` names, as of
+    /// `now`. Unknown times are shown as `???`.
+    pub fn format(&self, time: Option<SystemTime>, now: SystemTime) -> String {
+        self.format_in(time, now, &Local)
+    }
+
+    fn format_in<Tz: TimeZone>(
+        &self,
+        time: Option<SystemTime>,
+        now: SystemTime,
+        zone: &Tz,
+    ) -> String
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let Some(time) = time else {
+            return "???".to_string();
+        };
+        if *self == TimeStyle::Relative {
+            return format_relative(time, now);
+        }
+        let Some(datetime) = to_datetime(time) else {
+            return "???".to_string();
+        };
+
+        let recent = time <= now && now.duration_since(time).is_ok_and(|age| age < SIX_MONTHS);
+        let format = match self {
+            TimeStyle::Standard if recent => "%b %e %H:%M",
+            TimeStyle::Standard => "%b %e  %Y",
+            TimeStyle::FullIso => "%Y-%m-%d %H:%M:%S%.9f %z",
+            TimeStyle::LongIso => "%Y-%m-%d %H:%M",
+            TimeStyle::Iso if recent => "%m-%d %H:%M",
+            TimeStyle::Iso => "%Y-%m-%d ",
+            TimeStyle::Custom { recent: format, .. } if recent => format,
+            TimeStyle::Custom { old: format, .. } => format,
+            TimeStyle::Relative => unreachable!("relative times are formatted above"),
+        };
+        datetime.with_timezone(zone).format(format).to_string()
+    }
+}
+
+impl FromStr for TimeStyle {
+    type Err = String;
+
+    /// Parses the values `ls --time-style` accepts: `full-iso`, `long-iso`,
+    /// `iso`, `relative` or `+FORMAT`, where `FORMAT` may hold a format for
+    /// older times and one for recent times separated by a newline.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(TimeStyle::Standard),
+            "full-iso" => Ok(TimeStyle::FullIso),
+            "long-iso" => Ok(TimeStyle::LongIso),
+            "iso" => Ok(TimeStyle::Iso),
+            "relative" => Ok(TimeStyle::Relative),
+            _ => {
+                let Some(format) = s.strip_prefix('+') else {
+                    return Err(format!(
+                        "unknown time style '{}' (expected default, full-iso, long-iso, iso, relative or +FORMAT)",
+                        s
+                    ));
+                };
+                let (old, recent) = format.split_once('\n').unwrap_or((format, format));
+                for format in [old, recent] {
+                    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                        return Err(format!("invalid time format '{}'", format));
+                    }
+                }
+                Ok(TimeStyle::Custom {
+                    old: old.to_string(),
+                    recent: recent.to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Formats a timestamp like `ls` does, in the local time zone, or `???`
+/// when it is unknown.
+pub fn format_time(time: Option<SystemTime>) -> String {
+    TimeStyle::Standard.format(time, SystemTime::now())
+}
+
+/// Converts a time to a `DateTime`, or `None` if it's out of chrono's range.
+fn to_datetime(time: SystemTime) -> Option<DateTime<Utc>> {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => {
+            DateTime::from_timestamp(i64::try_from(since.as_secs()).ok()?, since.subsec_nanos())
+        }
+        Err(error) => {
+            let before = error.duration();
+            let secs = -i64::try_from(before.as_secs()).ok()?;
+            match before.subsec_nanos() {
+                0 => DateTime::from_timestamp(secs, 0),
+                nanos => DateTime::from_timestamp(secs - 1, 1_000_000_000 - nanos),
+            }
+        }
+    }
+}
+
+/// Formats how far `time` is from `now`, such as `5 minutes ago` or
+/// `in 2 days`, in the largest whole unit.
+fn format_relative(time: SystemTime, now: SystemTime) -> String {
+    const UNITS: &[(&str, u64)] = &[
+        ("year", 31_556_952),
+        ("month", 2_629_746),
+        ("week", 604_800),
+        ("day", 86_400),
+        ("hour", 3_600),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    let (seconds, future) = match now.duration_since(time) {
+        Ok(age) => (age.as_secs(), false),
+        Err(error) => (error.duration().as_secs(), true),
+    };
+    let Some((unit, unit_seconds)) = UNITS.iter().find(|(_, size)| seconds >= *size) else {
+        return "just now".to_string();
+    };
+
+    let count = seconds / unit_seconds;
+    let plural = if count == 1 { "" } else { "s" };
+    if future {
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+/// Formats a timestamp as RFC 3339 in UTC, for machine-readable output, or
+/// `None` if it's out of chrono's range.
+pub(crate) fn format_rfc3339(time: SystemTime) -> Option<String> {
+    to_datetime(time).map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size_bytes() {
+        assert_eq!(format_size(0, false), "0");
+        assert_eq!(format_size(512, false), "512");
+        assert_eq!(format_size(1023, false), "1023");
+    }
+
+    #[test]
+    fn test_format_size_human_readable() {
+        assert_eq!(format_size(0, true), "0B");
+        assert_eq!(format_size(512, true), "512B");
+        assert_eq!(format_size(1024, true), "1.0K");
+        assert_eq!(format_size(1536, true), "1.5K");
+        assert_eq!(format_size(1048576, true), "1.0M");
+        assert_eq!(format_size(1073741824, true), "1.0G");
+        assert_eq!(format_size(1099511627776, true), "1.0T");
+    }
+
+    #[test]
+    fn test_format_size_edge_cases() {
+        assert_eq!(format_size(1023, true), "1023B");
+        assert_eq!(format_size(1025, true), "1.0K");
+        assert_eq!(format_size(1048575, true), "1024.0K");
+        assert_eq!(format_size(1048577, true), "1.0M");
+    }
+
+    #[test]
+    fn test_format_mode() {
+        assert_eq!(format_mode(0o040755), "drwxr-xr-x");
+        assert_eq!(format_mode(0o100644), "-rw-r--r--");
+        assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+        assert_eq!(format_mode(0o010600), "prw-------");
+    }
+
+    #[test]
+    fn test_format_mode_special_bits() {
+        assert_eq!(format_mode(0o104755), "-rwsr-xr-x");
+        assert_eq!(format_mode(0o102644), "-rw-r-Sr--");
+        assert_eq!(format_mode(0o041777), "drwxrwxrwt");
+        assert_eq!(format_mode(0o041776), "drwxrwxrwT");
+    }
+
+    #[test]
+    fn test_format_rfc3339_out_of_range() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1640995200);
+        assert_eq!(format_rfc3339(time).unwrap(), "2022-01-01T00:00:00Z");
+        let far = SystemTime::UNIX_EPOCH + Duration::from_secs(99_999_999_999_999);
+        assert_eq!(format_rfc3339(far), None);
+    }
+
+    #[test]
+    fn test_format_time_none() {
+        assert_eq!(format_time(None), "???");
+    }
+
+    #[test]
+    fn test_format_time_some() {
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1640995200); // 2022-01-01 00:00:00 UTC
+        let formatted = format_time(Some(time));
+        // Older than six months, so the year is shown instead of the time
+        assert_eq!(formatted.len(), 12);
+        assert!(formatted.contains("2022") || formatted.contains("2021"));
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_time_style_recent_and_old() {
+        let now = at(1_704_462_312); // 2024-01-05 13:45:12 UTC
+        let recent = Some(at(1_704_462_312 - 86_400));
+        let old = Some(at(1_640_995_200));
+        let future = Some(at(1_704_462_312 + 86_400));
+
+        let format = |style: &TimeStyle, time| style.format_in(time, now, &Utc);
+        assert_eq!(format(&TimeStyle::Standard, recent), "Jan  4 13:45");
+        assert_eq!(format(&TimeStyle::Standard, old), "Jan  1  2022");
+        assert_eq!(format(&TimeStyle::Standard, future), "Jan  6  2024");
+        assert_eq!(format(&TimeStyle::Iso, recent), "01-04 13:45");
+        assert_eq!(format(&TimeStyle::Iso, old), "2022-01-01 ");
+        assert_eq!(format(&TimeStyle::LongIso, old), "2022-01-01 00:00");
+        assert_eq!(
+            format(&TimeStyle::FullIso, recent),
+            "2024-01-04 13:45:12.000000000 +0000"
+        );
+        assert_eq!(format(&TimeStyle::Standard, None), "???");
+    }
+
+    #[test]
+    fn test_time_style_time_zone() {
+        let now = at(1_704_462_312);
+        let zone = chrono::FixedOffset::east_opt(-5 * 3600).unwrap();
+        assert_eq!(
+            TimeStyle::LongIso.format_in(Some(now), now, &zone),
+            "2024-01-05 08:45"
+        );
+        // Before the epoch
+        assert_eq!(
+            TimeStyle::FullIso.format_in(
+                Some(SystemTime::UNIX_EPOCH - Duration::from_millis(1500)),
+                now,
+                &Utc
+            ),
+            "1969-12-31 23:59:58.500000000 +0000"
+        );
+    }
+
+    #[test]
+    fn test_time_style_relative() {
+        let now = at(1_704_462_312);
+        let format = |secs_ago: i64| {
+            let time = if secs_ago >= 0 {
+                now - Duration::from_secs(secs_ago as u64)
+            } else {
+                now + Duration::from_secs(secs_ago.unsigned_abs())
+            };
+            TimeStyle::Relative.format_in(Some(time), now, &Utc)
+        };
+        assert_eq!(format(0), "just now");
+        assert_eq!(format(1), "1 second ago");
+        assert_eq!(format(59), "59 seconds ago");
+        assert_eq!(format(3 * 3600 + 59), "3 hours ago");
+        assert_eq!(format(86_400), "1 day ago");
+        assert_eq!(format(400 * 86_400), "1 year ago");
+        assert_eq!(format(-120), "in 2 minutes");
+    }
+
+    #[test]
+    fn test_time_style_from_str() {
+        assert_eq!("iso".parse(), Ok(TimeStyle::Iso));
+        assert_eq!("relative".parse(), Ok(TimeStyle::Relative));
+        assert_eq!(
+            "+%Y".parse(),
+            Ok(TimeStyle::Custom {
+                old: "%Y".to_string(),
+                recent: "%Y".to_string()
+            })
+        );
+        assert_eq!(
+            "+%Y-%m-%d\n%m-%d %H:%M".parse(),
+            Ok(TimeStyle::Custom {
+                old: "%Y-%m-%d".to_string(),
+                recent: "%m-%d %H:%M".to_string()
+            })
+        );
+        assert!("+%Q".parse::<TimeStyle>().is_err());
+        assert!("posix".parse::<TimeStyle>().is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("0B"), Ok(0));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_size("4k"), Ok(4096));
+        assert_eq!(parse_size("2MiB"), parse_size("2MB"));
+        // What format_size prints parses back
+        assert_eq!(parse_size(&format_size(1536, true)), Ok(1536));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("1.2.3K").is_err());
+        assert!(parse_size("99999999P").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_ages() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100 * 86400);
+        let day = Duration::from_secs(86400);
+        assert_eq!(parse_time_in("3d", now, &Utc), Ok(now - 3 * day));
+        assert_eq!(parse_time_in("2w", now, &Utc), Ok(now - 14 * day));
+        assert_eq!(
+            parse_time_in("90 minutes", now, &Utc),
+            Ok(now - Duration::from_secs(5400))
+        );
+        assert!(parse_time_in("3x", now, &Utc).is_err());
+        assert!(parse_time_in("1000000000000y", now, &Utc).is_err());
+    }
+
+    #[test]
+    fn test_parse_time_dates() {
+        let now = SystemTime::now();
+        let zone = chrono::FixedOffset::east_opt(3600).unwrap();
+        let expected = |secs: u64| Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        // 2024-01-31T00:00:00+01:00
+        assert_eq!(
+            parse_time_in("2024-01-31", now, &zone),
+            expected(1_706_655_600)
+        );
+        assert_eq!(
+            parse_time_in("2024-01-31 12:30", now, &zone),
+            expected(1_706_655_600 + 45_000)
+        );
+        assert_eq!(
+            parse_time_in("2024-01-31T00:00:00Z", now, &zone),
+            expected(1_706_659_200)
+        );
+        assert!(parse_time_in("2024-02-30", now, &zone).is_err());
+    }
+}
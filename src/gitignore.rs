@@ -0,0 +1,218 @@
+//! Hiding entries matched by `.gitignore`, `.ignore` and other ignore files.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::{
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The ignore rules that apply to the entries of one directory.
+///
+/// Like git and ripgrep, `.ignore` files take precedence over `.gitignore`
+/// files, and a file in a deeper directory over those in its parents.
+/// `.git/info/exclude`, the global excludes file and explicit ignore files
+/// come last. `.gitignore` files, `.git/info/exclude` and the global excludes
+/// only apply inside a git repository.
+#[derive(Debug, Clone)]
+pub(crate) struct IgnoreRules {
+    /// The directory whose entries are matched, as an absolute path.
+    dir: PathBuf,
+    /// Whether to read ignore files from the directories being listed.
+    read_dirs: bool,
+    in_repo: bool,
+    /// Matchers from `.ignore` files, outermost directory first.
+    ignore: Vec<Arc<Gitignore>>,
+    /// Matchers from `.gitignore` files, outermost directory first.
+    gitignore: Vec<Arc<Gitignore>>,
+    /// The repository's `.git/info/exclude` and the global excludes file.
+    excludes: Vec<Arc<Gitignore>>,
+    /// Files given explicitly, which apply everywhere.
+    explicit: Arc<Vec<Gitignore>>,
+}
+
+impl IgnoreRules {
+    /// Collects the rules for the entries of `dir`.
+    ///
+    /// With `read_dirs` set, the ignore files in `dir` and every directory
+    /// above it are read. `files` are always used, with their patterns
+    /// relative to the directory each file is in. Ignore files that can't be
+    /// read or contain invalid patterns are skipped, keeping any valid
+    /// patterns.
+    pub(crate) fn new(dir: &Path, read_dirs: bool, files: &[PathBuf]) -> io::Result<Self> {
+        let dir = std::path::absolute(dir)?;
+        let mut rules = IgnoreRules {
+            dir: PathBuf::new(),
+            read_dirs,
+            in_repo: false,
+            ignore: Vec::new(),
+            gitignore: Vec::new(),
+            excludes: Vec::new(),
+            explicit: Arc::new(files.iter().map(|file| Gitignore::new(file).0).collect()),
+        };
+        if read_dirs {
+            let ancestors: Vec<&Path> = dir.ancestors().collect();
+            for ancestor in ancestors.into_iter().rev() {
+                rules.enter(ancestor);
+            }
+        }
+        rules.dir = dir;
+        Ok(rules)
+    }
+
+    /// The rules for the entries of the subdirectory `name`, adding its own
+    /// ignore files.
+    pub(crate) fn descend(&self, name: &OsStr) -> Self {
+        let mut rules = self.clone();
+        rules.dir = self.dir.join(name);
+        if rules.read_dirs {
+            let dir = rules.dir.clone();
+            rules.enter(&dir);
+        }
+        rules
+    }
+
+    /// Whether the entry `name` in this directory is ignored.
+    pub(crate) fn is_ignored(&self, name: &OsStr, is_dir: bool) -> bool {
+        let path = self.dir.join(name);
+        self.ignore
+            .iter()
+            .rev()
+            .chain(self.gitignore.iter().rev())
+            .chain(&self.excludes)
+            .map(Arc::as_ref)
+            .chain(self.explicit.iter())
+            .map(|matcher| matcher.matched(&path, is_dir))
+            .find(|matched| !matched.is_none())
+            .is_some_and(|matched| matched.is_ignore())
+    }
+
+    /// Adds the ignore files in `dir`, one level below the last directory
+    /// entered.
+    fn enter(&mut self, dir: &Path) {
+        // A nested repository doesn't use the ignore files of the one around it
+        if dir.join(".git").exists() {
+            self.in_repo = true;
+            self.gitignore.clear();
+            let (global, _) = GitignoreBuilder::new(dir).build_global();
+            self.excludes = vec![
+                Arc::new(Gitignore::new(dir.join(".git/info/exclude")).0),
+                Arc::new(global),
+            ];
+        }
+
+        push_matcher(&mut self.ignore, dir.join(".ignore"));
+        if self.in_repo {
+            push_matcher(&mut self.gitignore, dir.join(".gitignore"));
+        }
+    }
+}
+
+fn push_matcher(matchers: &mut Vec<Arc<Gitignore>>, file: PathBuf) {
+    if file.is_file() {
+        let (matcher, _) = Gitignore::new(file);
+        if !matcher.is_empty() {
+            matchers.push(Arc::new(matcher));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn ignored(rules: &IgnoreRules, names: &[(&str, bool)]) -> Vec<String> {
+        names
+            .iter()
+            .filter(|(name, is_dir)| rules.is_ignored(OsStr::new(name), *is_dir))
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_gitignore_semantics() {
+        let repo = tempdir().unwrap();
+        fs::create_dir(repo.path().join(".git")).unwrap();
+        fs::write(
+            repo.path().join(".gitignore"),
+            "*.log\n!keep.log\n/build\ncache/\n",
+        )
+        .unwrap();
+
+        let rules = IgnoreRules::new(repo.path(), true, &[]).unwrap();
+        assert_eq!(
+            ignored(
+                &rules,
+                &[
+                    ("a.log", false),
+                    ("keep.log", false),
+                    ("build", true),
+                    ("cache", true),
+                    ("cache", false),
+                    ("src", true),
+                ]
+            ),
+            ["a.log", "build", "cache"]
+        );
+
+        // Anchored patterns only match at the top, others at any depth
+        let rules = rules.descend(OsStr::new("src"));
+        assert_eq!(
+            ignored(
+                &rules,
+                &[("build", true), ("cache", true), ("b.log", false)]
+            ),
+            ["cache", "b.log"]
+        );
+    }
+
+    #[test]
+    fn test_nested_ignore_files_take_precedence() {
+        let repo = tempdir().unwrap();
+        fs::create_dir(repo.path().join(".git")).unwrap();
+        fs::create_dir_all(repo.path().join("sub")).unwrap();
+        fs::write(repo.path().join(".gitignore"), "*.tmp\nnotes\n").unwrap();
+        fs::write(repo.path().join("sub/.gitignore"), "!*.tmp\n").unwrap();
+        fs::write(repo.path().join("sub/.ignore"), "*.bak\n").unwrap();
+
+        // Listing the subdirectory directly still reads the parent's rules
+        let rules = IgnoreRules::new(&repo.path().join("sub"), true, &[]).unwrap();
+        assert_eq!(
+            ignored(
+                &rules,
+                &[("a.tmp", false), ("a.bak", false), ("notes", false)]
+            ),
+            ["a.bak", "notes"]
+        );
+    }
+
+    #[test]
+    fn test_gitignore_needs_repository() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.path().join(".ignore"), "*.bak\n").unwrap();
+
+        let rules = IgnoreRules::new(dir.path(), true, &[]).unwrap();
+        assert_eq!(
+            ignored(&rules, &[("a.log", false), ("a.bak", false)]),
+            ["a.bak"]
+        );
+    }
+
+    #[test]
+    fn test_explicit_ignore_file() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("patterns");
+        fs::write(&file, "node_modules/\n").unwrap();
+        fs::create_dir(dir.path().join("app")).unwrap();
+
+        let rules = IgnoreRules::new(&dir.path().join("app"), false, &[file]).unwrap();
+        assert_eq!(
+            ignored(&rules, &[("node_modules", true), ("index.js", false)]),
+            ["node_modules"]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! JSON and NDJSON output.
+
+use crate::{
+    entry::Entry,
+    format::{format_mode, format_rfc3339},
+    lister::TreeNode,
+    quote::is_unsafe_char,
+};
+use serde::Serialize;
+use serde_json::ser::{Formatter, Serializer};
+use std::{
+    ffi::OsStr,
+    io::{self, Write},
+};
+
+#[derive(Serialize)]
+struct JsonEntry {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_bytes: Option<Vec<u8>>,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_bytes: Option<Vec<u8>>,
+    #[serde(rename = "type")]
+    file_type: &'static str,
+    size: u64,
+    blocks: u64,
+    mode: u32,
+    permissions: String,
+    nlink: u64,
+    uid: u32,
+    gid: u32,
+    modified: Option<String>,
+    accessed: Option<String>,
+    changed: Option<String>,
+    created: Option<String>,
+    is_hidden: bool,
+    is_symlink: bool,
+    link_target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_target_bytes: Option<Vec<u8>>,
+    is_broken: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<JsonEntry>>,
+}
+
+impl JsonEntry {
+    fn new(entry: &Entry) -> Self {
+        JsonEntry {
+            name: entry.name.to_string_lossy().into_owned(),
+            name_bytes: raw_bytes(&entry.name),
+            path: entry.path.to_string_lossy().into_owned(),
+            path_bytes: raw_bytes(entry.path.as_os_str()),
+            file_type: entry.file_type().as_str(),
+            size: entry.size,
+            blocks: entry.blocks,
+            mode: entry.mode,
+            permissions: format_mode(entry.mode),
+            nlink: entry.nlink,
+            uid: entry.uid,
+            gid: entry.gid,
+            modified: entry.modified.and_then(format_rfc3339),
+            accessed: entry.accessed.and_then(format_rfc3339),
+            changed: entry.changed.and_then(format_rfc3339),
+            created: entry.created.and_then(format_rfc3339),
+            is_hidden: entry.is_hidden,
+            is_symlink: entry.is_symlink,
+            link_target: entry
+                .link_target
+                .as_ref()
+                .map(|target| target.to_string_lossy().into_owned()),
+            link_target_bytes: entry
+                .link_target
+                .as_ref()
+                .and_then(|target| raw_bytes(target.as_os_str())),
+            is_broken: entry.is_broken,
+            children: None,
+        }
+    }
+
+    fn with_children(node: &TreeNode) -> Self {
+        let mut json = JsonEntry::new(&node.entry);
+        if node.entry.is_dir {
+            json.children = Some(node.children.iter().map(JsonEntry::with_children).collect());
+        }
+        json
+    }
+}
+
+/// The bytes of `text` when it isn't valid UTF-8, so that the lossy string
+/// written alongside can be told apart from other names.
+fn raw_bytes(text: &OsStr) -> Option<Vec<u8>> {
+    text.to_str()
+        .is_none()
+        .then(|| text.as_encoded_bytes().to_vec())
+}
+
+/// Escapes every character that could affect a terminal as `\uXXXX`.
+///
+/// serde_json already escapes ASCII control characters, but passes DEL, C1
+/// controls and bidirectional overrides through as they are. Escaping them
+/// keeps names intact while making the output safe to print. Everything else
+/// is written compactly, as by `serde_json::to_writer`.
+struct EscapingFormatter;
+
+impl Formatter for EscapingFormatter {
+    fn write_string_fragment<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        let mut start = 0;
+        for (i, c) in fragment.char_indices() {
+            if is_unsafe_char(c) {
+                writer.write_all(&fragment.as_bytes()[start..i])?;
+                write!(writer, "\\u{:04x}", c as u32)?;
+                start = i + c.len_utf8();
+            }
+        }
+        writer.write_all(&fragment.as_bytes()[start..])
+    }
+}
+
+fn to_writer<W: Write, T: Serialize>(out: &mut W, value: &T) -> io::Result<()> {
+    value.serialize(&mut Serializer::with_formatter(out, EscapingFormatter))?;
+    Ok(())
+}
+
+/// Writes `entries` as a single JSON array.
+pub(crate) fn write_array<'a, W, I>(out: &mut W, entries: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Entry>,
+{
+    let entries: Vec<JsonEntry> = entries.into_iter().map(JsonEntry::new).collect();
+    to_writer(out, &entries)?;
+    writeln!(out)
+}
+
+/// Writes one JSON object per line, one line per entry.
+pub(crate) fn write_lines<'a, W, I>(out: &mut W, entries: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Entry>,
+{
+    for entry in entries {
+        to_writer(out, &JsonEntry::new(entry))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes `nodes` as a JSON array with directories nesting their children.
+pub(crate) fn write_tree<W: Write>(out: &mut W, nodes: &[TreeNode]) -> io::Result<()> {
+    let nodes: Vec<JsonEntry> = nodes.iter().map(JsonEntry::with_children).collect();
+    to_writer(out, &nodes)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    fn entry(name: &str, is_dir: bool) -> Entry {
+        let entry = if is_dir {
+            Entry::test_dir(name)
+        } else {
+            Entry::test_file(name)
+        };
+        Entry {
+            path: PathBuf::from("dir").join(name),
+            uid: 1000,
+            gid: 100,
+            size: 1536,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1640995200)),
+            ..entry
+        }
+    }
+
+    #[test]
+    fn test_write_array() {
+        let mut out = Vec::new();
+        write_array(&mut out, &[entry("a.rs", false)]).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let object = &value[0];
+        assert_eq!(object["name"], "a.rs");
+        assert_eq!(object["path"], "dir/a.rs");
+        assert_eq!(object["type"], "file");
+        assert_eq!(object["size"], 1536);
+        assert_eq!(object["permissions"], "-rw-r--r--");
+        assert_eq!(object["modified"], "2022-01-01T00:00:00Z");
+        assert_eq!(object["created"], serde_json::Value::Null);
+        assert_eq!(object["link_target"], serde_json::Value::Null);
+        assert!(object.get("children").is_none());
+        // Colors are never emitted
+        assert!(!out.contains(&0x1b));
+    }
+
+    #[test]
+    fn test_write_lines() {
+        let mut out = Vec::new();
+        write_lines(&mut out, &[entry("a", false), entry("b", true)]).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["type"], "directory");
+    }
+
+    #[test]
+    fn test_write_out_of_range_time_as_null() {
+        let mut far = entry("far", false);
+        far.modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(99_999_999_999_999));
+        let mut out = Vec::new();
+        write_array(&mut out, &[far]).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value[0]["modified"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_write_escapes_terminal_controls() {
+        let name = "a\x1b[31m\u{9b}\u{202e}b";
+        let mut out = Vec::new();
+        write_lines(&mut out, &[entry(name, false)]).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains(r#""name":"a\u001b[31m\u009b\u202eb""#));
+        // The name still round-trips exactly
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["name"], name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_non_utf8_names_as_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"caf\xe9");
+        let odd = Entry {
+            name: name.to_os_string(),
+            path: PathBuf::from(name),
+            ..entry("plain", false)
+        };
+        let mut out = Vec::new();
+        write_array(&mut out, &[odd, entry("plain", false)]).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value[0]["name"], "caf\u{fffd}");
+        assert_eq!(
+            value[0]["name_bytes"],
+            serde_json::json!([99, 97, 102, 233])
+        );
+        assert_eq!(value[0]["path_bytes"], value[0]["name_bytes"]);
+        assert!(value[0].get("link_target_bytes").is_none());
+        // Valid names have no byte form
+        assert!(value[1].get("name_bytes").is_none());
+    }
+
+    #[test]
+    fn test_write_tree_nests_children() {
+        let nodes = vec![TreeNode {
+            entry: entry("sub", true),
+            children: vec![TreeNode {
+                entry: entry("inner", false),
+                children: Vec::new(),
+            }],
+        }];
+
+        let mut out = Vec::new();
+        write_tree(&mut out, &nodes).unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value[0]["children"][0]["name"], "inner");
+        assert!(value[0]["children"][0].get("children").is_none());
+    }
+}
@@ -0,0 +1,43 @@
+//! Library behind the `zls` directory lister.
+//!
+//! [`Lister`] reads a directory into a list of [`Entry`] values and
+//! [`render`] writes them out in one of the supported layouts.
+
+mod colors;
+mod csv;
+mod entry;
+mod error;
+mod filter;
+mod format;
+mod gitignore;
+mod json;
+mod lister;
+mod natural;
+mod owner;
+mod query;
+mod quote;
+mod render;
+mod usage;
+mod width;
+
+#[cfg(all(test, unix))]
+#[path = "../tests/common/mod.rs"]
+mod test_support;
+
+pub use colors::{ColorMode, LsColors};
+pub use csv::Column;
+pub use entry::{Entry, FileType, TimeField};
+pub use error::{ErrorContext, ListError};
+pub use filter::{Filter, Pattern};
+pub use format::{format_mode, format_size, format_time, parse_size, parse_time, TimeStyle};
+pub use lister::{
+    group_entries, sort_entries, GroupBy, Lister, Listing, RecursiveListings, SortKey, Tree,
+    TreeNode,
+};
+pub use natural::{natural_cmp, version_cmp};
+pub use query::{Query, QueryError};
+pub use quote::QuotingStyle;
+pub use render::{
+    render, render_listings, render_operands, render_tree, render_trees, Layout, RenderOptions,
+};
+pub use usage::{disk_usage, DiskUsage};
@@ -0,0 +1,924 @@
+use crate::{
+    entry::{Entry, FileType, TimeField},
+    error::ListError,
+    filter::Filter,
+    gitignore::IgnoreRules,
+    natural::{natural_cmp, version_cmp},
+    usage::Totals,
+};
+use rayon::prelude::*;
+use std::{
+    cmp::Reverse,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The order in which a listing is returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// Case-insensitive name order.
+    #[default]
+    Name,
+    /// Largest first.
+    Size,
+    /// Newest first by the given timestamp, with unknown times last.
+    Time(TimeField),
+    /// Alphabetically by extension, with names that have none first.
+    Extension,
+    /// Case-insensitive name order with runs of digits compared as numbers,
+    /// so `file9` comes before `file10`.
+    Natural,
+    /// Version order like `ls -v`, which also compares runs of digits as
+    /// numbers but puts hidden names first and compares file suffixes last.
+    Version,
+    /// The order the directory returns them in.
+    None,
+}
+
+/// How sorted entries are gathered into groups.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    /// One group, in sort order.
+    #[default]
+    None,
+    /// Directories, then regular files, then symlinks and other types.
+    Type,
+    /// Alphabetically by lowercase extension, with names that have none
+    /// first.
+    Extension,
+}
+
+impl GroupBy {
+    /// A heading for the group `entry` belongs to, or `None` when entries
+    /// aren't grouped.
+    pub fn label(self, entry: &Entry) -> Option<String> {
+        match self {
+            GroupBy::None => None,
+            GroupBy::Type => Some(
+                match entry.file_type() {
+                    FileType::Directory => "Directories",
+                    FileType::File => "Files",
+                    FileType::Symlink => "Symlinks",
+                    FileType::Fifo => "FIFOs",
+                    FileType::Socket => "Sockets",
+                    FileType::BlockDevice => "Block devices",
+                    FileType::CharDevice => "Character devices",
+                }
+                .to_string(),
+            ),
+            GroupBy::Extension => match extension_key(entry).as_str() {
+                "" => Some("No extension".to_string()),
+                extension => Some(format!(".{} files", extension)),
+            },
+        }
+    }
+
+    fn key(self, entry: &Entry) -> (u8, String) {
+        match self {
+            GroupBy::None => (0, String::new()),
+            GroupBy::Type => {
+                let rank = match entry.file_type() {
+                    FileType::Directory => 0,
+                    FileType::File => 1,
+                    FileType::Symlink => 2,
+                    FileType::Fifo => 3,
+                    FileType::Socket => 4,
+                    FileType::BlockDevice => 5,
+                    FileType::CharDevice => 6,
+                };
+                (rank, String::new())
+            }
+            GroupBy::Extension => (0, extension_key(entry)),
+        }
+    }
+}
+
+/// The entries of a single directory, along with any that couldn't be read.
+#[derive(Debug)]
+pub struct Listing {
+    pub path: PathBuf,
+    pub entries: Vec<Entry>,
+    pub errors: Vec<ListError>,
+}
+
+/// An entry in a tree listing, along with the entries below it.
+#[derive(Debug, Clone)]
+pub struct TreeNode {
+    pub entry: Entry,
+    pub children: Vec<TreeNode>,
+}
+
+/// The result of a tree listing.
+#[derive(Debug)]
+pub struct Tree {
+    pub nodes: Vec<TreeNode>,
+    /// Entries and directories that couldn't be read, in tree order.
+    pub errors: Vec<ListError>,
+}
+
+/// Builder for directory listings.
+///
+/// ```no_run
+/// use zls::{Lister, SortKey, TimeField};
+///
+/// let listing = Lister::new()
+///     .all(true)
+///     .sort(SortKey::Time(TimeField::Modification))
+///     .list(".")?;
+/// for entry in &listing.entries {
+///     println!("{}", entry.name.to_string_lossy());
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Lister {
+    all: bool,
+    dereference: bool,
+    sort: SortKey,
+    reverse: bool,
+    group_by: GroupBy,
+    directories_first: bool,
+    git_ignore: bool,
+    ignore_files: Vec<PathBuf>,
+    filter: Filter,
+    total_size: bool,
+    cross_filesystems: bool,
+}
+
+impl Lister {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include hidden (dot) files in the listing.
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Describe what symlinks point to rather than the links themselves.
+    pub fn dereference(mut self, dereference: bool) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    /// Set the order entries are returned in.
+    pub fn sort(mut self, sort: SortKey) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Return entries in the opposite of the sort order.
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Gather entries into groups, keeping the sort order within each.
+    pub fn group_by(mut self, group_by: GroupBy) -> Self {
+        self.group_by = group_by;
+        self
+    }
+
+    /// Put directories before everything else, ahead of any other grouping.
+    pub fn group_directories_first(mut self, directories_first: bool) -> Self {
+        self.directories_first = directories_first;
+        self
+    }
+
+    /// Hide entries matched by `.gitignore`, `.ignore`, `.git/info/exclude`
+    /// and the global git excludes file, with the same precedence as git.
+    ///
+    /// Ignore files in the listed directory and the directories above it
+    /// apply, as do those in subdirectories reached by
+    /// [`Lister::list_recursive`] and [`Lister::tree`].
+    pub fn git_ignore(mut self, git_ignore: bool) -> Self {
+        self.git_ignore = git_ignore;
+        self
+    }
+
+    /// Hide entries matched by the gitignore-style patterns in `path`, which
+    /// are relative to the directory the file is in.
+    ///
+    /// Can be given more than once. These patterns apply everywhere, after
+    /// those from [`Lister::git_ignore`].
+    pub fn ignore_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.ignore_files.push(path.into());
+        self
+    }
+
+    /// List only the entries `filter` keeps, matching names and paths
+    /// relative to the directory being listed.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Report each directory's size as the total of everything below it,
+    /// like `du`, in both [`Entry::size`] and [`Entry::blocks`].
+    ///
+    /// Totals are computed before filtering and sorting, so size conditions
+    /// and [`SortKey::Size`] use them. As with [`crate::disk_usage`], they
+    /// stay on the filesystem of the directory being listed, so a mount point
+    /// below it only counts itself. A file hard-linked into several of its
+    /// subdirectories counts only toward the first.
+    ///
+    /// Subdirectories are totalled in parallel, and in recursive and tree
+    /// listings what's below each directory is only walked once.
+    pub fn total_size(mut self, total_size: bool) -> Self {
+        self.total_size = total_size;
+        self
+    }
+
+    /// Include directories on other filesystems in [`Lister::total_size`]
+    /// totals, which otherwise stop at mount points.
+    pub fn cross_filesystems(mut self, cross_filesystems: bool) -> Self {
+        self.cross_filesystems = cross_filesystems;
+        self
+    }
+
+    /// Reads the directory at `path`, reading entry metadata in parallel.
+    ///
+    /// Fails only if the directory itself can't be read. Entries whose
+    /// metadata can't be read are reported in [`Listing::errors`] instead.
+    pub fn list<P: AsRef<Path>>(&self, path: P) -> io::Result<Listing> {
+        let path = path.as_ref();
+        let rules = self.ignore_rules(path)?;
+        let totals = Totals::new(self.cross_filesystems);
+        let mut listing = self.read(path, path, rules.as_ref(), &totals)?;
+        self.retain_matches(&mut listing.entries, path);
+        Ok(listing)
+    }
+
+    fn ignore_rules(&self, path: &Path) -> io::Result<Option<IgnoreRules>> {
+        if !self.git_ignore && self.ignore_files.is_empty() {
+            return Ok(None);
+        }
+        IgnoreRules::new(path, self.git_ignore, &self.ignore_files).map(Some)
+    }
+
+    /// Reads the directory at `path`, which is `root` or a directory below it.
+    fn read(
+        &self,
+        path: &Path,
+        root: &Path,
+        rules: Option<&IgnoreRules>,
+        totals: &Totals,
+    ) -> io::Result<Listing> {
+        // Read the names up front so unsorted listings keep the directory order
+        let dir_entries: Vec<io::Result<fs::DirEntry>> = fs::read_dir(path)?.collect();
+        let results: Vec<Result<Entry, ListError>> = dir_entries
+            .into_par_iter()
+            .map(|entry| {
+                let entry_path = entry
+                    .map_err(|error| ListError::access(path.to_path_buf(), error))?
+                    .path();
+                let result = if self.dereference {
+                    Entry::from_path_dereferenced(entry_path.clone())
+                } else {
+                    Entry::from_path(entry_path.clone())
+                };
+                result.map_err(|error| ListError::access(entry_path, error))
+            })
+            .collect();
+
+        let mut entries = Vec::with_capacity(results.len());
+        let mut errors = Vec::new();
+        for result in results {
+            match result {
+                Ok(entry) => entries.push(entry),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if !self.all {
+            entries.retain(|file| !file.is_hidden);
+            errors.retain(|error| !is_hidden_path(&error.path) || error.path == path);
+        }
+        if let Some(rules) = rules {
+            entries.retain(|entry| !rules.is_ignored(&entry.name, entry.is_dir));
+            errors.retain(|error| {
+                error.path == path
+                    || !error
+                        .path
+                        .file_name()
+                        .is_some_and(|name| rules.is_ignored(name, false))
+            });
+        }
+        // Other filter conditions are checked later, as entries below a
+        // directory that doesn't match can still be listed
+        if !self.filter.is_empty() {
+            entries.retain(|entry| {
+                !self
+                    .filter
+                    .excludes(&entry.name, relative(&entry.path, root))
+            });
+            errors.retain(|error| {
+                error.path == path
+                    || !error
+                        .path
+                        .file_name()
+                        .is_some_and(|name| self.filter.excludes(name, relative(&error.path, root)))
+            });
+        }
+
+        if self.total_size {
+            let dirs: Vec<usize> = (0..entries.len())
+                .filter(|&i| entries[i].is_dir && (!entries[i].is_symlink || self.dereference))
+                .collect();
+            let paths: Vec<&Path> = dirs.iter().map(|&i| entries[i].path.as_path()).collect();
+            let usages = totals.siblings(path, &paths);
+            for (i, usage) in dirs.into_iter().zip(usages) {
+                entries[i].size = usage.size;
+                entries[i].blocks = usage.blocks;
+            }
+        }
+
+        sort_entries(&mut entries, self.sort, self.reverse);
+        group_entries(&mut entries, self.group_by, self.directories_first);
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Listing {
+            path: path.to_path_buf(),
+            entries,
+            errors,
+        })
+    }
+
+    /// Lists `path` and every subdirectory below it, like `ls -R`.
+    ///
+    /// Sibling subtrees are walked in parallel, but the result is always in
+    /// depth-first order with each directory's children following the sort
+    /// order. Symlinks to directories are not descended into, and neither are
+    /// directories hidden by [`Lister::git_ignore`] or an excluded pattern,
+    /// while directories hidden by the other [`Filter`] conditions still are.
+    /// Only a failure to read `path` itself is returned as an error;
+    /// subdirectories that can't be read are reported in their listing's
+    /// `errors`.
+    pub fn list_recursive<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<Listing>> {
+        let path = path.as_ref();
+        let rules = self.ignore_rules(path)?;
+        let totals = Totals::new(self.cross_filesystems);
+        let listing = self.read(path, path, rules.as_ref(), &totals)?;
+        Ok(self.descend(listing, path, rules.as_ref(), &totals))
+    }
+
+    /// Like [`Lister::list_recursive`], but returns the listings one at a time
+    /// as they're read rather than all at once, so output can start before
+    /// the whole tree has been walked.
+    ///
+    /// The listings come in the same order. Directories are read one after
+    /// another, each with its entries' metadata read in parallel.
+    pub fn iter_recursive<P: AsRef<Path>>(&self, path: P) -> io::Result<RecursiveListings<'_>> {
+        let path = path.as_ref();
+        let rules = self.ignore_rules(path)?;
+        let totals = Totals::new(self.cross_filesystems);
+        let listing = self.read(path, path, rules.as_ref(), &totals)?;
+        let mut listings = RecursiveListings {
+            lister: self,
+            root: path.to_path_buf(),
+            first: None,
+            pending: Vec::new(),
+            totals,
+        };
+        listings.first = Some(listings.expand(listing, rules.as_ref()));
+        Ok(listings)
+    }
+
+    fn descend(
+        &self,
+        listing: Listing,
+        root: &Path,
+        rules: Option<&IgnoreRules>,
+        totals: &Totals,
+    ) -> Vec<Listing> {
+        let subdirs: Vec<&Entry> = listing
+            .entries
+            .iter()
+            .filter(|entry| entry.is_dir && !entry.is_symlink)
+            .collect();
+        let children: Vec<Vec<Listing>> = subdirs
+            .par_iter()
+            .map(|dir| {
+                let rules = rules.map(|rules| rules.descend(&dir.name));
+                match self.read(&dir.path, root, rules.as_ref(), totals) {
+                    Ok(child) => self.descend(child, root, rules.as_ref(), totals),
+                    Err(error) => vec![Listing {
+                        path: dir.path.clone(),
+                        entries: Vec::new(),
+                        errors: vec![ListError::open_directory(dir.path.clone(), error)],
+                    }],
+                }
+            })
+            .collect();
+
+        let mut listing = listing;
+        self.retain_matches(&mut listing.entries, root);
+        let mut listings = vec![listing];
+        listings.extend(children.into_iter().flatten());
+        listings
+    }
+
+    /// Builds the hierarchy below `path` for a tree view.
+    ///
+    /// `max_depth` limits how many levels are read, with `Some(1)` returning
+    /// only the immediate children of `path`. As with
+    /// [`Lister::list_recursive`], only a failure to read `path` itself is
+    /// returned as an error. Directories that don't match the [`Filter`] are
+    /// kept when entries below them do.
+    pub fn tree<P: AsRef<Path>>(&self, path: P, max_depth: Option<usize>) -> io::Result<Tree> {
+        let path = path.as_ref();
+        let rules = self.ignore_rules(path)?;
+        let totals = Totals::new(self.cross_filesystems);
+        let listing = self.read(path, path, rules.as_ref(), &totals)?;
+        let (nodes, errors) = self.tree_level(listing, path, rules.as_ref(), &totals, max_depth);
+        Ok(Tree { nodes, errors })
+    }
+
+    fn tree_level(
+        &self,
+        listing: Listing,
+        root: &Path,
+        rules: Option<&IgnoreRules>,
+        totals: &Totals,
+        max_depth: Option<usize>,
+    ) -> (Vec<TreeNode>, Vec<ListError>) {
+        let remaining = max_depth.map(|depth| depth.saturating_sub(1));
+        let mut errors = listing.errors;
+        let results: Vec<(TreeNode, Vec<ListError>)> = listing
+            .entries
+            .into_par_iter()
+            .map(|entry| {
+                if !entry.is_dir || entry.is_symlink || remaining == Some(0) {
+                    let node = TreeNode {
+                        entry,
+                        children: Vec::new(),
+                    };
+                    return (node, Vec::new());
+                }
+                let rules = rules.map(|rules| rules.descend(&entry.name));
+                match self.read(&entry.path, root, rules.as_ref(), totals) {
+                    Ok(child) => {
+                        let (children, errors) =
+                            self.tree_level(child, root, rules.as_ref(), totals, remaining);
+                        (TreeNode { entry, children }, errors)
+                    }
+                    Err(error) => {
+                        let error = ListError::open_directory(entry.path.clone(), error);
+                        let node = TreeNode {
+                            entry,
+                            children: Vec::new(),
+                        };
+                        (node, vec![error])
+                    }
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(results.len());
+        for (node, node_errors) in results {
+            // Keep directories that lead to matching entries
+            if !node.children.is_empty()
+                || self
+                    .filter
+                    .matches(&node.entry, relative(&node.entry.path, root))
+            {
+                nodes.push(node);
+            }
+            errors.extend(node_errors);
+        }
+        (nodes, errors)
+    }
+
+    fn retain_matches(&self, entries: &mut Vec<Entry>, root: &Path) {
+        if !self.filter.is_empty() {
+            entries.retain(|entry| self.filter.matches(entry, relative(&entry.path, root)));
+        }
+    }
+}
+
+/// The listings of a directory and every subdirectory below it, read lazily
+/// in depth-first order. Returned by [`Lister::iter_recursive`].
+#[derive(Debug)]
+pub struct RecursiveListings<'a> {
+    lister: &'a Lister,
+    root: PathBuf,
+    /// The listing of the root, read up front so failing to read it is an
+    /// error.
+    first: Option<Listing>,
+    /// Directories still to be read, the next one last.
+    pending: Vec<(PathBuf, Option<IgnoreRules>)>,
+    totals: Totals,
+}
+
+impl RecursiveListings<'_> {
+    /// Queues the subdirectories of `listing` and then applies the filter.
+    fn expand(&mut self, mut listing: Listing, rules: Option<&IgnoreRules>) -> Listing {
+        let subdirs = listing
+            .entries
+            .iter()
+            .filter(|entry| entry.is_dir && !entry.is_symlink)
+            .rev()
+            .map(|dir| {
+                (
+                    dir.path.clone(),
+                    rules.map(|rules| rules.descend(&dir.name)),
+                )
+            });
+        self.pending.extend(subdirs);
+        self.lister.retain_matches(&mut listing.entries, &self.root);
+        listing
+    }
+}
+
+impl Iterator for RecursiveListings<'_> {
+    type Item = Listing;
+
+    fn next(&mut self) -> Option<Listing> {
+        if let Some(listing) = self.first.take() {
+            return Some(listing);
+        }
+        let (dir, rules) = self.pending.pop()?;
+        Some(
+            match self
+                .lister
+                .read(&dir, &self.root, rules.as_ref(), &self.totals)
+            {
+                Ok(listing) => self.expand(listing, rules.as_ref()),
+                Err(error) => Listing {
+                    path: dir.clone(),
+                    entries: Vec::new(),
+                    errors: vec![ListError::open_directory(dir, error)],
+                },
+            },
+        )
+    }
+}
+
+/// `path` relative to the directory a listing started from.
+fn relative<'a>(path: &'a Path, root: &Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+fn is_hidden_path(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+/// Sorts `entries` in place by `key`, or in the opposite order if `reverse`
+/// is set.
+///
+/// Entries that compare equal are ordered by name, so the result doesn't
+/// depend on the order they were read in. [`SortKey::None`] leaves entries
+/// as they are, even when reversed, like `ls -U`.
+pub fn sort_entries(entries: &mut [Entry], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Name => entries.sort_by_cached_key(name_key),
+        SortKey::Size => entries.sort_by_cached_key(|entry| (Reverse(entry.size), name_key(entry))),
+        SortKey::Time(field) => {
+            entries.sort_by_cached_key(|entry| (Reverse(entry.time(field)), name_key(entry)))
+        }
+        SortKey::Extension => {
+            entries.sort_by_cached_key(|entry| (extension_key(entry), name_key(entry)))
+        }
+        SortKey::Natural => entries.sort_by(|a, b| natural_cmp(&a.name, &b.name)),
+        SortKey::Version => entries.sort_by(|a, b| version_cmp(&a.name, &b.name)),
+        SortKey::None => return,
+    }
+    if reverse {
+        entries.reverse();
+    }
+}
+
+/// Moves sorted `entries` into groups by `group_by`, with directories ahead
+/// of everything else if `directories_first` is set.
+///
+/// Entries keep their sort order within each group, and the groups
+/// themselves are never reversed.
+pub fn group_entries(entries: &mut [Entry], group_by: GroupBy, directories_first: bool) {
+    if group_by == GroupBy::None && !directories_first {
+        return;
+    }
+    // A stable sort, so the order within groups is kept
+    entries.sort_by_cached_key(|entry| (directories_first && !entry.is_dir, group_by.key(entry)));
+}
+
+/// Case-insensitive name order, falling back to the exact name so names that
+/// only differ in case or invalid bytes still sort consistently.
+fn name_key(entry: &Entry) -> (String, OsString) {
+    (
+        entry.name.to_string_lossy().to_lowercase(),
+        entry.name.clone(),
+    )
+}
+
+/// The lowercase extension, empty for names without one. A leading dot, as
+/// in `.bashrc`, doesn't start an extension.
+fn extension_key(entry: &Entry) -> String {
+    Path::new(&entry.name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_list_hides_dotfiles_by_default() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("visible"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+
+        let names: Vec<_> = Lister::new()
+            .list(dir.path())
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["visible"]);
+
+        let names: Vec<_> = Lister::new()
+            .all(true)
+            .list(dir.path())
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, [".hidden", "visible"]);
+    }
+
+    #[test]
+    fn test_sort_by_name_ignores_case() {
+        let dir = tempdir().unwrap();
+        for name in ["b", "C", "a"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let names: Vec<_> = Lister::new()
+            .list(dir.path())
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["a", "b", "C"]);
+    }
+
+    #[test]
+    fn test_list_recursive_is_depth_first() {
+        let dir = tempdir().unwrap();
+        for sub in ["b", "a", "a/inner", ".hidden"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        fs::write(dir.path().join("a/inner/file"), "").unwrap();
+        fs::write(dir.path().join(".hidden/file"), "").unwrap();
+
+        let listings = Lister::new().list_recursive(dir.path()).unwrap();
+        let paths: Vec<_> = listings
+            .iter()
+            .map(|l| l.path.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::new(),
+                PathBuf::from("a"),
+                PathBuf::from("a/inner"),
+                PathBuf::from("b"),
+            ]
+        );
+        assert_eq!(listings[2].entries[0].name, "file");
+
+        let listings = Lister::new().all(true).list_recursive(dir.path()).unwrap();
+        assert_eq!(listings.len(), 5);
+        assert!(listings[1].path.ends_with(".hidden"));
+    }
+
+    #[test]
+    fn test_iter_recursive_matches_list_recursive() {
+        let dir = tempdir().unwrap();
+        for sub in ["b", "a", "a/inner", "a/inner/deep", "c"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        fs::write(dir.path().join("a/inner/file"), "").unwrap();
+
+        let lister = Lister::new().sort(SortKey::Name);
+        let paths = |listings: Vec<Listing>| -> Vec<PathBuf> {
+            listings.into_iter().map(|listing| listing.path).collect()
+        };
+        assert_eq!(
+            paths(lister.iter_recursive(dir.path()).unwrap().collect()),
+            paths(lister.list_recursive(dir.path()).unwrap())
+        );
+        assert!(lister.iter_recursive(dir.path().join("missing")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_recursive_skips_symlinked_dirs() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("real/loop")).unwrap();
+
+        let listings = Lister::new().list_recursive(dir.path()).unwrap();
+        assert_eq!(listings.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_dereference() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("real")).unwrap();
+        std::os::unix::fs::symlink("real", dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("dangling")).unwrap();
+
+        let entries = Lister::new().list(dir.path()).unwrap().entries;
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|e| e.name == "real" || !e.is_dir));
+
+        let entries = Lister::new()
+            .dereference(true)
+            .list(dir.path())
+            .unwrap()
+            .entries;
+        assert!(entries.iter().any(|e| e.name == "link" && e.is_dir));
+        assert!(entries.iter().any(|e| e.name == "dangling" && e.is_broken));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_subdir_is_reported_not_fatal() {
+        use std::os::unix::fs::PermissionsExt;
+
+        if crate::test_support::running_as_root() {
+            return;
+        }
+        let dir = tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(dir.path().join("file"), "").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let listings = Lister::new().list_recursive(dir.path()).unwrap();
+        assert_eq!(listings.len(), 2);
+        assert_eq!(listings[0].entries.len(), 2);
+        assert_eq!(listings[1].errors[0].path, locked);
+
+        let tree = Lister::new().tree(dir.path(), None).unwrap();
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(tree.errors.len(), 1);
+
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unstattable_entries_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a"), "").unwrap();
+        fs::write(dir.path().join(".b"), "").unwrap();
+        // Without execute permission names can be read but not stat'd
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o600)).unwrap();
+        if fs::metadata(dir.path().join("a")).is_ok() {
+            fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+            return;
+        }
+
+        let listing = Lister::new().list(dir.path()).unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(listing.entries.is_empty());
+        assert_eq!(listing.errors.len(), 1);
+        assert_eq!(listing.errors[0].path, dir.path().join("a"));
+    }
+
+    #[test]
+    fn test_tree_respects_max_depth() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+
+        let nodes = Lister::new().tree(dir.path(), None).unwrap().nodes;
+        assert_eq!(nodes[0].children[0].children[0].entry.name, "c");
+
+        let nodes = Lister::new().tree(dir.path(), Some(2)).unwrap().nodes;
+        assert_eq!(nodes[0].children[0].entry.name, "b");
+        assert!(nodes[0].children[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_sort_by_time_newest_first() {
+        let mut entries = named(&["old", "new"]);
+        entries[0].modified = Some(SystemTime::UNIX_EPOCH);
+        entries[1].modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+
+        sort_entries(&mut entries, SortKey::Time(TimeField::Modification), false);
+        assert_eq!(entries[0].name, "new");
+        assert_eq!(entries[1].name, "old");
+    }
+
+    #[test]
+    fn test_sort_by_selected_time() {
+        let mut entries = named(&["a", "b", "c"]);
+        entries[0].accessed = Some(SystemTime::UNIX_EPOCH);
+        entries[1].modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(120));
+        entries[2].accessed = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+        entries[2].modified = Some(SystemTime::UNIX_EPOCH);
+
+        sort_entries(&mut entries, SortKey::Time(TimeField::Access), false);
+        let names: Vec<_> = entries.iter().map(|e| e.name.clone()).collect();
+        assert_eq!(names, ["c", "a", "b"]);
+    }
+
+    fn named(names: &[&str]) -> Vec<Entry> {
+        names.iter().map(|name| Entry::test_file(name)).collect()
+    }
+
+    fn names(entries: &[Entry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| entry.name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_by_size_breaks_ties_by_name() {
+        let mut entries = named(&["b", "c", "a", "big"]);
+        entries[3].size = 10;
+
+        sort_entries(&mut entries, SortKey::Size, false);
+        assert_eq!(names(&entries), ["big", "a", "b", "c"]);
+
+        sort_entries(&mut entries, SortKey::Size, true);
+        assert_eq!(names(&entries), ["c", "b", "a", "big"]);
+    }
+
+    #[test]
+    fn test_sort_by_extension() {
+        let mut entries = named(&["b.txt", "a.rs", "Makefile", ".bashrc", "c.RS", "a.txt"]);
+        sort_entries(&mut entries, SortKey::Extension, false);
+        assert_eq!(
+            names(&entries),
+            [".bashrc", "Makefile", "a.rs", "c.RS", "a.txt", "b.txt"]
+        );
+    }
+
+    #[test]
+    fn test_sort_by_version() {
+        let mut entries = named(&["v1.10.0", "v1.9.0", "v1.9.10", "file010", "file9", "v1.9.2"]);
+        sort_entries(&mut entries, SortKey::Version, false);
+        assert_eq!(
+            names(&entries),
+            ["file9", "file010", "v1.9.0", "v1.9.2", "v1.9.10", "v1.10.0"]
+        );
+    }
+
+    #[test]
+    fn test_sort_natural() {
+        let mut entries = named(&["file10", "File9", "file1", "apple"]);
+        sort_entries(&mut entries, SortKey::Natural, false);
+        assert_eq!(names(&entries), ["apple", "file1", "File9", "file10"]);
+
+        sort_entries(&mut entries, SortKey::Natural, true);
+        assert_eq!(names(&entries), ["file10", "File9", "file1", "apple"]);
+    }
+
+    #[test]
+    fn test_group_entries_keeps_sort_order() {
+        let mut entries = named(&["b.rs", "a.txt", "c", "d.rs", "sub"]);
+        entries[4] = Entry::test_dir("sub");
+
+        sort_entries(&mut entries, SortKey::Name, true);
+        group_entries(&mut entries, GroupBy::None, true);
+        assert_eq!(names(&entries), ["sub", "d.rs", "c", "b.rs", "a.txt"]);
+
+        group_entries(&mut entries, GroupBy::Extension, false);
+        assert_eq!(names(&entries), ["sub", "c", "d.rs", "b.rs", "a.txt"]);
+
+        group_entries(&mut entries, GroupBy::Type, false);
+        assert_eq!(names(&entries), ["sub", "c", "d.rs", "b.rs", "a.txt"]);
+        assert_eq!(
+            GroupBy::Type.label(&entries[0]).as_deref(),
+            Some("Directories")
+        );
+        assert_eq!(
+            GroupBy::Extension.label(&entries[2]).as_deref(),
+            Some(".rs files")
+        );
+        assert_eq!(GroupBy::None.label(&entries[2]), None);
+    }
+
+    #[test]
+    fn test_sort_none_keeps_order() {
+        let mut entries = named(&["b", "c", "a"]);
+        sort_entries(&mut entries, SortKey::None, true);
+        assert_eq!(names(&entries), ["b", "c", "a"]);
+    }
+}
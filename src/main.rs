@@ -1,31 +1,488 @@
-use clap::Parser;
-use crossterm::{
-    style::{Color, Stylize},
-    terminal,
-};
-use rayon::prelude::*;
+use clap::{Parser, ValueEnum};
+use crossterm::terminal;
 use std::{
     fs,
-    path::{Path, PathBuf},
+    io::{self, IsTerminal, Write},
+    iter,
+    path::PathBuf,
+    process::ExitCode,
     time::SystemTime,
 };
+use zls::{
+    group_entries, parse_size, parse_time, render, render_listings, render_operands, render_tree,
+    render_trees, sort_entries, ColorMode, Column, Entry, FileType, Filter, GroupBy, Layout,
+    ListError, Lister, Listing, LsColors, Pattern, Query, QuotingStyle, RenderOptions, SortKey,
+    TimeField, TimeStyle, TreeNode,
+};
+
+/// Exit status for minor problems, such as an entry that can't be read.
+const EXIT_MINOR: u8 = 1;
+/// Exit status for serious trouble, such as a path operand that can't be read.
+const EXIT_SERIOUS: u8 = 2;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Detailed listing, one entry per line
+    Long,
+    /// Names in columns, ordered down each column
+    Short,
+    /// Names in columns, ordered across each row
+    Across,
+    /// One name per line
+    SingleColumn,
+    /// A JSON array of entries
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated values
+    Csv,
+    /// Tab-separated values
+    Tsv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorWhen {
+    /// Color only when writing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Quoting {
+    /// Names as they are
+    Literal,
+    /// Quote names for a shell when needed
+    Shell,
+    /// Quote names for a shell, escaping unprintable characters
+    ShellEscape,
+    /// Double-quote names with C escapes
+    C,
+    /// C escapes without quotes
+    Escape,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TimeWord {
+    /// When the contents last changed
+    #[value(alias = "mtime")]
+    Modification,
+    /// When the file was last read
+    #[value(aliases = ["atime", "use"])]
+    Access,
+    /// When the contents or metadata last changed
+    #[value(aliases = ["ctime", "status"])]
+    Change,
+    /// When the file was created
+    #[value(aliases = ["btime", "creation"])]
+    Birth,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortWord {
+    /// Case-insensitive name order
+    Name,
+    /// Largest first
+    Size,
+    /// Newest first, by the time chosen with --time
+    Time,
+    /// By extension
+    Extension,
+    /// Case-insensitive, with numbers within names compared by value
+    Natural,
+    /// Version order, like sort -V
+    Version,
+    /// Directory order
+    None,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GroupWord {
+    /// Directories, then files, then other types
+    Type,
+    /// By extension
+    Extension,
+    /// No grouping
+    None,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TypeWord {
+    /// Regular file
+    #[value(name = "f")]
+    File,
+    /// Directory
+    #[value(name = "d")]
+    Directory,
+    /// Symbolic link
+    #[value(name = "l")]
+    Symlink,
+    /// Named pipe
+    #[value(name = "p")]
+    Fifo,
+    /// Socket
+    #[value(name = "s")]
+    Socket,
+    /// Block device
+    #[value(name = "b")]
+    BlockDevice,
+    /// Character device
+    #[value(name = "c")]
+    CharDevice,
+}
+
+/// The sort options, of which the last one given wins.
+const SORT_OPTIONS: [&str; 6] = [
+    "sort",
+    "sort_by_time",
+    "sort_by_size",
+    "sort_by_extension",
+    "sort_by_version",
+    "unsorted",
+];
 
 #[derive(Parser)]
 #[command(name = "zls")]
 #[command(about = "A fast ls replacement written in Rust")]
 #[command(version)]
 struct Args {
-    #[arg(help = "Directory to list", default_value = ".")]
-    path: PathBuf,
+    #[arg(help = "Files and directories to list", default_value = ".")]
+    paths: Vec<PathBuf>,
 
     #[arg(short, long, help = "Show hidden files")]
     all: bool,
 
-    #[arg(short, long, help = "Use short listing format")]
+    #[arg(
+        short,
+        long,
+        help = "List names in columns, ordered down",
+        overrides_with_all = ["across", "single_column"]
+    )]
     short: bool,
 
-    #[arg(short, long, help = "Sort by modification time")]
-    time: bool,
+    #[arg(
+        short = 'x',
+        help = "List names in columns, ordered across",
+        overrides_with_all = ["short", "single_column"],
+        conflicts_with = "format"
+    )]
+    across: bool,
+
+    #[arg(
+        short = '1',
+        help = "List one name per line",
+        overrides_with_all = ["short", "across"],
+        conflicts_with = "format"
+    )]
+    single_column: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        help = "Output format",
+        conflicts_with = "short"
+    )]
+    format: Option<Format>,
+
+    #[arg(
+        long,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        help = "Columns to write in csv and tsv formats [default: name,path,type,size,permissions,user,group,mtime]"
+    )]
+    columns: Vec<Column>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_value = "auto",
+        default_missing_value = "always",
+        help = "When to color output"
+    )]
+    color: ColorWhen,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Truncate names wider than N columns with an ellipsis",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    max_name_width: Option<u64>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        default_value = "literal",
+        help = "How to quote file names"
+    )]
+    quoting_style: Quoting,
+
+    #[arg(
+        short = 'q',
+        long,
+        help = "Print ? instead of control characters in names [default when output is a terminal]",
+        overrides_with = "show_control_chars"
+    )]
+    hide_control_chars: bool,
+
+    #[arg(
+        long,
+        help = "Print control characters in names as they are [default when output is not a terminal]",
+        overrides_with = "hide_control_chars"
+    )]
+    show_control_chars: bool,
+
+    #[arg(
+        long,
+        value_name = "STYLE",
+        default_value = "default",
+        help = "How to show times: default, full-iso, long-iso, iso, relative or +FORMAT (strftime)"
+    )]
+    time_style: TimeStyle,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WORD",
+        help = "Sort order",
+        overrides_with_all = SORT_OPTIONS
+    )]
+    sort: Option<SortWord>,
+
+    #[arg(
+        short = 't',
+        help = "Sort by time, newest first",
+        overrides_with_all = SORT_OPTIONS
+    )]
+    sort_by_time: bool,
+
+    #[arg(
+        short = 'S',
+        help = "Sort by size, largest first",
+        overrides_with_all = SORT_OPTIONS
+    )]
+    sort_by_size: bool,
+
+    #[arg(
+        short = 'X',
+        help = "Sort by extension",
+        overrides_with_all = SORT_OPTIONS
+    )]
+    sort_by_extension: bool,
+
+    #[arg(
+        short = 'v',
+        help = "Sort by version, with numbers within names compared by value",
+        overrides_with_all = SORT_OPTIONS
+    )]
+    sort_by_version: bool,
+
+    #[arg(
+        short = 'U',
+        help = "Don't sort; list entries in directory order",
+        overrides_with_all = SORT_OPTIONS
+    )]
+    unsorted: bool,
+
+    #[arg(short = 'r', long, help = "Reverse the sort order")]
+    reverse: bool,
+
+    #[arg(long, help = "List directories before other entries")]
+    group_directories_first: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WORD",
+        help = "Gather entries into groups, keeping the sort order within each"
+    )]
+    group_by: Option<GroupWord>,
+
+    #[arg(
+        long,
+        help = "Write a heading above each group in the long format",
+        requires = "group_by"
+    )]
+    group_headers: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WORD",
+        help = "Which time to show and sort by with -t",
+        overrides_with_all = ["access_time", "change_time"]
+    )]
+    time: Option<TimeWord>,
+
+    #[arg(
+        short = 'u',
+        help = "Show and sort by access time, like --time=access",
+        overrides_with_all = ["time", "change_time"]
+    )]
+    access_time: bool,
+
+    #[arg(
+        short = 'c',
+        help = "Show and sort by status change time, like --time=change",
+        overrides_with_all = ["time", "access_time"]
+    )]
+    change_time: bool,
+
+    #[arg(
+        short = 'L',
+        long,
+        help = "Show information for the file a symlink points to"
+    )]
+    dereference: bool,
+
+    #[arg(
+        long,
+        help = "Show each directory's size as the total of everything below it, like du"
+    )]
+    total_size: bool,
+
+    #[arg(
+        long,
+        requires = "total_size",
+        help = "Include other filesystems mounted below a directory in --total-size"
+    )]
+    cross_filesystems: bool,
+
+    #[arg(
+        long,
+        help = "Hide entries matched by .gitignore, .ignore and git's exclude files"
+    )]
+    git_ignore: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Hide entries matched by the gitignore-style patterns in PATH",
+        value_parser = parse_ignore_file
+    )]
+    ignore_file: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "List only entries matching GLOB (repeatable)",
+        value_parser = parse_glob
+    )]
+    include: Vec<Pattern>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Like --include, ignoring case",
+        value_parser = parse_glob_nocase
+    )]
+    iinclude: Vec<Pattern>,
+
+    #[arg(
+        short = 'I',
+        long,
+        alias = "ignore",
+        value_name = "GLOB",
+        help = "Hide entries matching GLOB (repeatable)",
+        value_parser = parse_glob
+    )]
+    exclude: Vec<Pattern>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        help = "Like --exclude, ignoring case",
+        value_parser = parse_glob_nocase
+    )]
+    iexclude: Vec<Pattern>,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help = "List only entries whose name, or path in recursive listings, matches PATTERN",
+        value_parser = parse_regex
+    )]
+    regex: Vec<Pattern>,
+
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        help = "Like --regex, ignoring case",
+        value_parser = parse_regex_nocase
+    )]
+    iregex: Vec<Pattern>,
+
+    #[arg(
+        long = "type",
+        value_enum,
+        value_name = "TYPE",
+        value_delimiter = ',',
+        help = "List only entries of these types"
+    )]
+    file_type: Vec<TypeWord>,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        help = "List only entries of at least SIZE, such as 10M or 1.5G",
+        value_parser = parse_size
+    )]
+    min_size: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        help = "List only entries of at most SIZE",
+        value_parser = parse_size
+    )]
+    max_size: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "AGE|DATE",
+        help = "List only entries newer than an age such as 3d or 2w, or a date",
+        value_parser = parse_time_arg
+    )]
+    newer_than: Option<SystemTime>,
+
+    #[arg(
+        long,
+        value_name = "AGE|DATE",
+        help = "List only entries older than an age or date",
+        value_parser = parse_time_arg
+    )]
+    older_than: Option<SystemTime>,
+
+    #[arg(
+        long = "where",
+        value_name = "EXPR",
+        help = "List only entries matching EXPR, such as 'size > 10M && mtime < now - 7d'"
+    )]
+    queries: Vec<Query>,
+
+    #[arg(short = 'R', long, help = "List subdirectories recursively")]
+    recursive: bool,
+
+    #[arg(
+        long,
+        help = "Show the directory hierarchy as a tree",
+        conflicts_with = "recursive"
+    )]
+    tree: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Descend at most N levels in tree view",
+        requires = "tree",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    level: Option<u64>,
 
     #[arg(
         short = 'H',
@@ -37,270 +494,364 @@ struct Args {
 
     #[arg(long, help = "Show raw byte sizes instead of human readable")]
     bytes: bool,
-}
-
-#[derive(Debug)]
-struct FileInfo {
-    name: String,
-    #[allow(dead_code)]
-    path: PathBuf,
-    is_dir: bool,
-    is_hidden: bool,
-    size: u64,
-    modified: Option<SystemTime>,
-}
 
-impl FileInfo {
-    fn from_path(path: PathBuf) -> Result<Self, std::io::Error> {
-        let metadata = fs::metadata(&path)?;
-        let name = path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-
-        let is_hidden = name.starts_with('.');
-        let is_dir = metadata.is_dir();
-        let size = metadata.len();
-        let modified = metadata.modified().ok();
-
-        Ok(FileInfo {
-            name,
-            path,
-            is_dir,
-            is_hidden,
-            size,
-            modified,
-        })
-    }
+    #[arg(
+        short = 'n',
+        long,
+        help = "Show numeric user and group ids instead of names"
+    )]
+    numeric_uid_gid: bool,
 }
 
-fn format_size(size: u64, human: bool) -> String {
-    if !human {
-        return size.to_string();
-    }
-
-    const UNITS: &[&str] = &["B", "K", "M", "G", "T"];
-    let mut size = size as f64;
-    let mut unit_index = 0;
-
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
-    }
-
-    if unit_index == 0 {
-        format!("{:.0}{}", size, UNITS[unit_index])
-    } else {
-        format!("{:.1}{}", size, UNITS[unit_index])
-    }
-}
+fn main() -> ExitCode {
+    let args = Args::parse();
 
-fn format_time(time: Option<SystemTime>) -> String {
-    match time {
-        Some(t) => {
-            let duration = t.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
-            let datetime =
-                chrono::DateTime::from_timestamp(duration.as_secs() as i64, 0).unwrap_or_default();
-            datetime.format("%b %d %H:%M").to_string()
+    match run(&args) {
+        Ok(code) => code,
+        // Output was closed early, e.g. piped into `head`
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("zls: {}", error);
+            ExitCode::from(EXIT_SERIOUS)
         }
-        None => "???".to_string(),
     }
 }
 
-fn list_directory(path: &Path, args: &Args) -> Result<Vec<FileInfo>, std::io::Error> {
-    let entries: Result<Vec<_>, _> = fs::read_dir(path)?
-        .par_bridge()
-        .map(|entry| {
-            let entry = entry?;
-            FileInfo::from_path(entry.path())
+fn run(args: &Args) -> io::Result<ExitCode> {
+    let time_field = match args.time {
+        Some(TimeWord::Modification) => TimeField::Modification,
+        Some(TimeWord::Access) => TimeField::Access,
+        Some(TimeWord::Change) => TimeField::Change,
+        Some(TimeWord::Birth) => TimeField::Birth,
+        None if args.access_time => TimeField::Access,
+        None if args.change_time => TimeField::Change,
+        None => TimeField::Modification,
+    };
+    let sort = match args.sort {
+        Some(SortWord::Name) => SortKey::Name,
+        Some(SortWord::Size) => SortKey::Size,
+        Some(SortWord::Time) => SortKey::Time(time_field),
+        Some(SortWord::Extension) => SortKey::Extension,
+        Some(SortWord::Natural) => SortKey::Natural,
+        Some(SortWord::Version) => SortKey::Version,
+        Some(SortWord::None) => SortKey::None,
+        None if args.sort_by_time => SortKey::Time(time_field),
+        None if args.sort_by_size => SortKey::Size,
+        None if args.sort_by_extension => SortKey::Extension,
+        None if args.sort_by_version => SortKey::Version,
+        None if args.unsorted => SortKey::None,
+        None => SortKey::Name,
+    };
+    // Like ls, an unsorted listing is never grouped
+    let group_by = match args.group_by {
+        Some(GroupWord::Type) if sort != SortKey::None => GroupBy::Type,
+        Some(GroupWord::Extension) if sort != SortKey::None => GroupBy::Extension,
+        _ => GroupBy::None,
+    };
+    let directories_first = args.group_directories_first && sort != SortKey::None;
+    let filter = [&args.include, &args.iinclude, &args.regex, &args.iregex]
+        .into_iter()
+        .flatten()
+        .fold(Filter::new(), |filter, pattern| {
+            filter.include(pattern.clone())
+        });
+    let filter = [&args.exclude, &args.iexclude]
+        .into_iter()
+        .flatten()
+        .fold(filter, |filter, pattern| filter.exclude(pattern.clone()));
+    let mut filter = args
+        .file_type
+        .iter()
+        .fold(filter, |filter, file_type| {
+            filter.file_type(match file_type {
+                TypeWord::File => FileType::File,
+                TypeWord::Directory => FileType::Directory,
+                TypeWord::Symlink => FileType::Symlink,
+                TypeWord::Fifo => FileType::Fifo,
+                TypeWord::Socket => FileType::Socket,
+                TypeWord::BlockDevice => FileType::BlockDevice,
+                TypeWord::CharDevice => FileType::CharDevice,
+            })
         })
-        .collect();
-
-    let mut entries = entries?;
-
-    if !args.all {
-        entries.retain(|file| !file.is_hidden);
+        .time_field(time_field);
+    if let Some(size) = args.min_size {
+        filter = filter.min_size(size);
     }
-
-    if args.time {
-        entries.sort_by(|a, b| {
-            b.modified
-                .unwrap_or(SystemTime::UNIX_EPOCH)
-                .cmp(&a.modified.unwrap_or(SystemTime::UNIX_EPOCH))
-        });
-    } else {
-        entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    if let Some(size) = args.max_size {
+        filter = filter.max_size(size);
     }
-
-    Ok(entries)
-}
-
-fn print_entries(entries: &[FileInfo], args: &Args) {
-    let term_width = terminal::size().map(|(w, _)| w as usize).unwrap_or(80);
-
-    if !args.short {
-        for entry in entries {
-            let size_str = format_size(entry.size, args.human && !args.bytes);
-            let time_str = format_time(entry.modified);
-            let type_char = if entry.is_dir { "d" } else { "-" };
-
-            let name = if entry.is_dir {
-                entry.name.clone().with(Color::Blue).bold().to_string()
-            } else if entry.name.ends_with(".rs") {
-                entry.name.clone().with(Color::Yellow).to_string()
-            } else if entry.name.starts_with('.') {
-                entry.name.clone().with(Color::DarkGrey).to_string()
+    if let Some(time) = args.newer_than {
+        filter = filter.newer_than(time);
+    }
+    if let Some(time) = args.older_than {
+        filter = filter.older_than(time);
+    }
+    let filter = args
+        .queries
+        .iter()
+        .fold(filter, |filter, query| filter.query(query.clone()));
+    let lister = args
+        .ignore_file
+        .iter()
+        .fold(Lister::new(), |lister, file| lister.ignore_file(file));
+    let lister = lister
+        .all(args.all)
+        .dereference(args.dereference)
+        .total_size(args.total_size)
+        .cross_filesystems(args.cross_filesystems)
+        .sort(sort)
+        .reverse(args.reverse)
+        .group_by(group_by)
+        .group_directories_first(directories_first)
+        .git_ignore(args.git_ignore)
+        .filter(filter);
+
+    let options = RenderOptions {
+        layout: match args.format {
+            Some(Format::Long) => Layout::Long,
+            Some(Format::Short) => Layout::Short,
+            Some(Format::Across) => Layout::Across,
+            Some(Format::SingleColumn) => Layout::SingleColumn,
+            Some(Format::Json) => Layout::Json,
+            Some(Format::Ndjson) => Layout::Ndjson,
+            Some(Format::Csv) => Layout::Csv,
+            Some(Format::Tsv) => Layout::Tsv,
+            None if args.short => Layout::Short,
+            None if args.across => Layout::Across,
+            None if args.single_column => Layout::SingleColumn,
+            None => Layout::Long,
+        },
+        human: args.human && !args.bytes,
+        numeric_ids: args.numeric_uid_gid,
+        term_width: terminal::size().map(|(w, _)| w as usize).unwrap_or(80),
+        columns: if args.columns.is_empty() {
+            Column::DEFAULT.to_vec()
+        } else {
+            args.columns.clone()
+        },
+        colors: color_mode(args.color)
+            .enabled(io::stdout().is_terminal())
+            .then(LsColors::from_env),
+        max_name_width: args.max_name_width.map(|width| width as usize),
+        time_field,
+        time_style: args.time_style.clone(),
+        quoting: match args.quoting_style {
+            Quoting::Literal => QuotingStyle::Literal,
+            Quoting::Shell => QuotingStyle::Shell,
+            Quoting::ShellEscape => QuotingStyle::ShellEscape,
+            Quoting::C => QuotingStyle::C,
+            Quoting::Escape => QuotingStyle::Escape,
+        },
+        hide_control_chars: args.hide_control_chars
+            || (!args.show_control_chars && io::stdout().is_terminal()),
+        group_headers: if args.group_headers {
+            group_by
+        } else {
+            GroupBy::None
+        },
+    };
+
+    let mut status = Status {
+        code: 0,
+        hide_control_chars: !args.show_control_chars && io::stderr().is_terminal(),
+    };
+    let (mut files, mut dirs) = operands(args, options.layout == Layout::Long, &mut status);
+    sort_entries(&mut files, sort, args.reverse);
+    sort_entries(&mut dirs, sort, args.reverse);
+    group_entries(&mut files, group_by, directories_first);
+
+    let mut stdout = io::stdout().lock();
+    if args.tree {
+        let mut roots: Vec<TreeNode> = files
+            .into_iter()
+            .map(|entry| TreeNode {
+                entry,
+                children: Vec::new(),
+            })
+            .collect();
+        let single = roots.is_empty() && dirs.len() == 1;
+        for dir in dirs {
+            match lister.tree(&dir.path, args.level.map(|level| level as usize)) {
+                Ok(tree) => {
+                    status.report(&tree.errors, EXIT_MINOR);
+                    roots.push(TreeNode {
+                        entry: dir,
+                        children: tree.nodes,
+                    });
+                }
+                Err(error) => status.open_failed(&dir, error),
+            }
+        }
+        // A lone directory keeps the plain layout, with its contents at the top
+        match roots.as_slice() {
+            [root] if single => {
+                render_tree(&mut stdout, &root.entry.path, &root.children, &options)?
+            }
+            _ => render_trees(&mut stdout, &roots, &options)?,
+        }
+    } else if options.layout == Layout::Ndjson {
+        // Each directory's entries are written as soon as it has been read
+        render(&mut stdout, &files, &options)?;
+        for dir in &dirs {
+            let listed: io::Result<Box<dyn Iterator<Item = Listing>>> = if args.recursive {
+                lister
+                    .iter_recursive(&dir.path)
+                    .map(|listings| Box::new(listings) as Box<_>)
             } else {
-                entry.name.clone()
+                lister
+                    .list(&dir.path)
+                    .map(|listing| Box::new(iter::once(listing)) as Box<_>)
             };
-
-            println!("{} {:>8} {} {}", type_char, size_str, time_str, name);
+            match listed {
+                Ok(listings) => {
+                    for listing in listings {
+                        status.report(&listing.errors, EXIT_MINOR);
+                        render(&mut stdout, &listing.entries, &options)?;
+                        stdout.flush()?;
+                    }
+                }
+                Err(error) => status.open_failed(dir, error),
+            }
         }
     } else {
-        let mut current_width = 0;
-        for (i, entry) in entries.iter().enumerate() {
-            let name = if entry.is_dir {
-                format!("{}/", entry.name)
-                    .with(Color::Blue)
-                    .bold()
-                    .to_string()
-            } else if entry.name.ends_with(".rs") {
-                entry.name.clone().with(Color::Yellow).to_string()
-            } else if entry.name.starts_with('.') {
-                entry.name.clone().with(Color::DarkGrey).to_string()
+        let headers = args.recursive || !files.is_empty() || dirs.len() > 1;
+        let mut listings: Vec<Listing> = Vec::new();
+        for dir in &dirs {
+            let listed = if args.recursive {
+                lister.list_recursive(&dir.path)
             } else {
-                entry.name.clone()
+                lister.list(&dir.path).map(|listing| vec![listing])
             };
-
-            let display_width = entry.name.len() + if entry.is_dir { 1 } else { 0 };
-
-            if current_width + display_width + 2 > term_width && i > 0 {
-                println!();
-                current_width = 0;
+            match listed {
+                Ok(dir_listings) => {
+                    for listing in &dir_listings {
+                        status.report(&listing.errors, EXIT_MINOR);
+                    }
+                    listings.extend(dir_listings);
+                }
+                Err(error) => status.open_failed(dir, error),
             }
-
-            print!("{:<width$}", name, width = display_width + 2);
-            current_width += display_width + 2;
         }
-        if !entries.is_empty() {
-            println!();
+        if args.recursive && files.is_empty() {
+            render_listings(&mut stdout, &listings, &options)?;
+        } else {
+            render_operands(&mut stdout, &files, &listings, headers, &options)?;
         }
     }
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-
-    if !args.path.exists() {
-        eprintln!("Error: Path '{}' does not exist", args.path.display());
-        std::process::exit(1);
-    }
-
-    if !args.path.is_dir() {
-        eprintln!("Error: '{}' is not a directory", args.path.display());
-        std::process::exit(1);
-    }
+    stdout.flush()?;
 
-    let entries = list_directory(&args.path, &args)?;
-    print_entries(&entries, &args);
-
-    Ok(())
+    Ok(ExitCode::from(status.code))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::path::PathBuf;
-    use std::time::SystemTime;
-
-    #[test]
-    fn test_format_size_bytes() {
-        assert_eq!(format_size(0, false), "0");
-        assert_eq!(format_size(512, false), "512");
-        assert_eq!(format_size(1023, false), "1023");
+/// Splits the path operands into files and directories, reporting the ones
+/// that can't be accessed.
+///
+/// Each entry is named after the operand as it was given, and directory
+/// operands are described by what they point to. Like `ls -l`, the long
+/// layout shows a symlink to a directory as the link itself unless
+/// dereferencing.
+fn operands(args: &Args, long: bool, status: &mut Status) -> (Vec<Entry>, Vec<Entry>) {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for path in &args.paths {
+        let metadata = if long && !args.dereference {
+            fs::symlink_metadata(path)
+        } else {
+            fs::metadata(path)
+        };
+        let is_dir = match metadata {
+            Ok(metadata) => metadata.is_dir(),
+            // A broken symlink is still listed as a file, unless it has to
+            // be followed
+            Err(_) if path.is_symlink() && !args.dereference => false,
+            Err(error) => {
+                status.report(&[ListError::access(path.clone(), error)], EXIT_SERIOUS);
+                continue;
+            }
+        };
+        let entry = if is_dir || args.dereference {
+            Entry::from_path_dereferenced(path.clone())
+        } else {
+            Entry::from_path(path.clone())
+        };
+        match entry {
+            Ok(mut entry) => {
+                entry.name = path.clone().into_os_string();
+                if is_dir {
+                    dirs.push(entry);
+                } else {
+                    files.push(entry);
+                }
+            }
+            Err(error) => status.report(&[ListError::access(path.clone(), error)], EXIT_SERIOUS),
+        }
     }
+    (files, dirs)
+}
 
-    #[test]
-    fn test_format_size_human_readable() {
-        assert_eq!(format_size(0, true), "0B");
-        assert_eq!(format_size(512, true), "512B");
-        assert_eq!(format_size(1024, true), "1.0K");
-        assert_eq!(format_size(1536, true), "1.5K");
-        assert_eq!(format_size(1048576, true), "1.0M");
-        assert_eq!(format_size(1073741824, true), "1.0G");
-        assert_eq!(format_size(1099511627776, true), "1.0T");
-    }
+/// The exit status so far, raised as problems are reported.
+struct Status {
+    code: u8,
+    /// Replace control characters in messages, as they include file names.
+    hide_control_chars: bool,
+}
 
-    #[test]
-    fn test_format_time_none() {
-        assert_eq!(format_time(None), "???");
+impl Status {
+    /// Prints errors to stderr the way `ls` does, raising the exit status to
+    /// at least `code` if there were any.
+    fn report<E: std::fmt::Display>(&mut self, errors: &[E], code: u8) {
+        for error in errors {
+            let message = error.to_string();
+            if self.hide_control_chars {
+                eprintln!("zls: {}", message.replace(char::is_control, "?"));
+            } else {
+                eprintln!("zls: {}", message);
+            }
+            self.code = self.code.max(code);
+        }
     }
 
-    #[test]
-    fn test_format_time_some() {
-        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1640995200); // 2022-01-01 00:00:00 UTC
-        let formatted = format_time(Some(time));
-        // Should contain month, day, hour, minute format
-        assert!(formatted.len() > 5);
-        assert!(formatted.contains(':'));
+    /// Reports that a directory operand itself couldn't be read.
+    fn open_failed(&mut self, dir: &Entry, error: io::Error) {
+        self.report(
+            &[ListError::open_directory(dir.path.clone(), error)],
+            EXIT_SERIOUS,
+        );
     }
+}
 
-    #[test]
-    fn test_file_info_hidden_detection() {
-        // Test that files starting with '.' are detected as hidden
-        let path = PathBuf::from(".hidden_file");
-
-        // We'll create a mock FileInfo since we can't guarantee the file exists
-        let file_info = FileInfo {
-            name: ".hidden_file".to_string(),
-            path: path.clone(),
-            is_dir: false,
-            is_hidden: true,
-            size: 0,
-            modified: None,
-        };
+fn parse_time_arg(s: &str) -> Result<SystemTime, String> {
+    parse_time(s, SystemTime::now())
+}
 
-        assert!(file_info.is_hidden);
-        assert_eq!(file_info.name, ".hidden_file");
-    }
+fn parse_glob(glob: &str) -> Result<Pattern, String> {
+    Pattern::glob(glob, false)
+}
 
-    #[test]
-    fn test_file_info_regular_file() {
-        let file_info = FileInfo {
-            name: "regular_file.txt".to_string(),
-            path: PathBuf::from("regular_file.txt"),
-            is_dir: false,
-            is_hidden: false,
-            size: 1024,
-            modified: Some(SystemTime::now()),
-        };
+fn parse_glob_nocase(glob: &str) -> Result<Pattern, String> {
+    Pattern::glob(glob, true)
+}
 
-        assert!(!file_info.is_hidden);
-        assert!(!file_info.is_dir);
-        assert_eq!(file_info.size, 1024);
-    }
+fn parse_regex(pattern: &str) -> Result<Pattern, String> {
+    Pattern::regex(pattern, false)
+}
 
-    #[test]
-    fn test_file_info_directory() {
-        let file_info = FileInfo {
-            name: "directory".to_string(),
-            path: PathBuf::from("directory"),
-            is_dir: true,
-            is_hidden: false,
-            size: 4096,
-            modified: Some(SystemTime::now()),
-        };
+fn parse_regex_nocase(pattern: &str) -> Result<Pattern, String> {
+    Pattern::regex(pattern, true)
+}
 
-        assert!(!file_info.is_hidden);
-        assert!(file_info.is_dir);
-    }
+/// Checks that an ignore file can be read, so a mistyped path isn't silently
+/// treated as an empty file.
+fn parse_ignore_file(path: &str) -> Result<PathBuf, String> {
+    fs::File::open(path)
+        .map(|_| PathBuf::from(path))
+        .map_err(|error| error.to_string())
+}
 
-    #[test]
-    fn test_format_size_edge_cases() {
-        assert_eq!(format_size(1023, true), "1023B");
-        assert_eq!(format_size(1025, true), "1.0K");
-        assert_eq!(format_size(1048575, true), "1024.0K");
-        assert_eq!(format_size(1048577, true), "1.0M");
+fn color_mode(when: ColorWhen) -> ColorMode {
+    match when {
+        ColorWhen::Auto => ColorMode::Auto,
+        ColorWhen::Always => ColorMode::Always,
+        ColorWhen::Never => ColorMode::Never,
     }
 }
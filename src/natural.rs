@@ -0,0 +1,256 @@
+//! Name comparisons that order runs of digits by value.
+//!
+//! Both comparisons work on the raw bytes of the names and never allocate,
+//! so they're cheap enough to call for every comparison in a sort.
+
+use std::{cmp::Ordering, ffi::OsStr};
+
+/// Compares names the way `ls -v` and `sort -V` do, following GNU
+/// `filevercmp`.
+///
+/// Runs of digits are compared by value and letters sort before other
+/// characters, with `~` sorting before everything, even the end of the name.
+/// `.` and `..` come first, then other hidden names, and file suffixes such
+/// as `.tar.gz` are only compared when the rest of the names are equal.
+/// Names that are still equal, like `a1` and `a01`, are ordered by their
+/// bytes.
+pub fn version_cmp(a: &OsStr, b: &OsStr) -> Ordering {
+    let (a, b) = (a.as_encoded_bytes(), b.as_encoded_bytes());
+    filevercmp(a, b).then_with(|| a.cmp(b))
+}
+
+/// Compares names with runs of digits by value and other text ignoring ASCII
+/// case, so `File9` comes before `file10`.
+///
+/// Names that are otherwise equal are ordered by their bytes.
+pub fn natural_cmp(a: &OsStr, b: &OsStr) -> Ordering {
+    let (a, b) = (a.as_encoded_bytes(), b.as_encoded_bytes());
+    compare_runs(a, b, |c| c.to_ascii_lowercase() as i32).then_with(|| a.cmp(b))
+}
+
+fn filevercmp(a: &[u8], b: &[u8]) -> Ordering {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (false, false) => {}
+    }
+
+    // "." sorts first, then "..", then other hidden names, then the rest
+    match (a[0] == b'.', b[0] == b'.') {
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (true, true) => {
+            for special in [&b"."[..], b".."] {
+                match (a == special, b == special) {
+                    (true, true) => return Ordering::Equal,
+                    (true, false) => return Ordering::Less,
+                    (false, true) => return Ordering::Greater,
+                    (false, false) => {}
+                }
+            }
+        }
+        (false, false) => {}
+    }
+
+    let (a_prefix, b_prefix) = (&a[..prefix_len(a)], &b[..prefix_len(b)]);
+    let result = compare_runs(a_prefix, b_prefix, version_order);
+    if result.is_ne() || (a_prefix.len() == a.len() && b_prefix.len() == b.len()) {
+        result
+    } else {
+        compare_runs(a, b, version_order)
+    }
+}
+
+/// The length of `name` without its file suffix, which is a trailing run of
+/// `.` followed by a letter or `~` and then letters, digits or `~`.
+fn prefix_len(name: &[u8]) -> usize {
+    let is_suffix_start = |c: u8| c.is_ascii_alphabetic() || c == b'~';
+    let is_suffix_char = |c: u8| c.is_ascii_alphanumeric() || c == b'~';
+
+    let mut prefix_len = 0;
+    let mut i = 0;
+    while i < name.len() {
+        i += 1;
+        prefix_len = i;
+        while i + 1 < name.len() && name[i] == b'.' && is_suffix_start(name[i + 1]) {
+            i += 2;
+            while i < name.len() && is_suffix_char(name[i]) {
+                i += 1;
+            }
+        }
+    }
+    prefix_len
+}
+
+/// How a non-digit byte sorts in version order: `~` first, then letters,
+/// then everything else.
+fn version_order(c: u8) -> i32 {
+    if c.is_ascii_alphabetic() {
+        c as i32
+    } else if c == b'~' {
+        -1
+    } else {
+        c as i32 + 256
+    }
+}
+
+/// Compares alternating runs of non-digits and digits, like Debian's
+/// `verrevcmp`.
+///
+/// Non-digit runs are compared byte by byte using `order`, where the end of a
+/// run sorts as 0. Digit runs are compared by value, ignoring leading zeros.
+fn compare_runs(a: &[u8], b: &[u8], order: impl Fn(u8) -> i32) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while a.get(i).is_some_and(|c| !c.is_ascii_digit())
+            || b.get(j).is_some_and(|c| !c.is_ascii_digit())
+        {
+            let a_order = a
+                .get(i)
+                .filter(|c| !c.is_ascii_digit())
+                .map_or(0, |&c| order(c));
+            let b_order = b
+                .get(j)
+                .filter(|c| !c.is_ascii_digit())
+                .map_or(0, |&c| order(c));
+            if a_order != b_order {
+                return a_order.cmp(&b_order);
+            }
+            i += 1;
+            j += 1;
+        }
+
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+
+        let mut first_diff = Ordering::Equal;
+        while let (Some(x), Some(y)) = (a.get(i), b.get(j)) {
+            if !x.is_ascii_digit() || !y.is_ascii_digit() {
+                break;
+            }
+            first_diff = first_diff.then(x.cmp(y));
+            i += 1;
+            j += 1;
+        }
+        // A longer number is bigger
+        if a.get(i).is_some_and(u8::is_ascii_digit) {
+            return Ordering::Greater;
+        }
+        if b.get(j).is_some_and(u8::is_ascii_digit) {
+            return Ordering::Less;
+        }
+        if first_diff.is_ne() {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(cmp: fn(&OsStr, &OsStr) -> Ordering, names: &[&str]) -> Vec<String> {
+        let mut names: Vec<&OsStr> = names.iter().map(OsStr::new).collect();
+        names.sort_by(|a, b| cmp(a, b));
+        names
+            .iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_version_cmp_numbers() {
+        assert_eq!(
+            sorted(version_cmp, &["file10", "file2", "file1", "file02"]),
+            ["file1", "file02", "file2", "file10"]
+        );
+        assert_eq!(
+            sorted(version_cmp, &["v1.10.0", "v1.9.0", "v1.9.10", "v1.9.2"]),
+            ["v1.9.0", "v1.9.2", "v1.9.10", "v1.10.0"]
+        );
+        assert_eq!(
+            version_cmp(
+                OsStr::new("99999999999999999999999"),
+                OsStr::new("100000000000000000000000")
+            ),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_version_cmp_matches_filevercmp() {
+        // Expected orders taken from GNU `ls -v`
+        assert_eq!(
+            sorted(
+                version_cmp,
+                &["b", "a~", "a", ".hidden", "..", ".", "1", "B", "a.b", "~", "a+"]
+            ),
+            [".", "..", ".hidden", "~", "1", "B", "a~", "a", "a.b", "a+", "b"]
+        );
+        // Suffixes are compared only after the rest of the name
+        assert_eq!(
+            sorted(
+                version_cmp,
+                &[
+                    "zls-1.10.tar.gz",
+                    "zls-1.9.tar.xz",
+                    "zls-1.9.tar.gz",
+                    "zls-1.9~rc1.tar.gz"
+                ]
+            ),
+            [
+                "zls-1.9~rc1.tar.gz",
+                "zls-1.9.tar.gz",
+                "zls-1.9.tar.xz",
+                "zls-1.10.tar.gz"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_version_cmp_equal_versions_fall_back_to_bytes() {
+        assert_eq!(
+            version_cmp(OsStr::new("a01"), OsStr::new("a1")),
+            Ordering::Less
+        );
+        assert_eq!(
+            version_cmp(OsStr::new("a1"), OsStr::new("a1")),
+            Ordering::Equal
+        );
+        assert_eq!(version_cmp(OsStr::new(""), OsStr::new("a")), Ordering::Less);
+    }
+
+    #[test]
+    fn test_prefix_len() {
+        assert_eq!(prefix_len(b"zls-1.9.tar.gz"), 7);
+        assert_eq!(prefix_len(b"a.b"), 1);
+        assert_eq!(prefix_len(b"a.1"), 3);
+        assert_eq!(prefix_len(b"file"), 4);
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(
+            sorted(
+                natural_cmp,
+                &["file10", "File9", "file1", "apple", "Banana"]
+            ),
+            ["apple", "Banana", "file1", "File9", "file10"]
+        );
+        assert_eq!(
+            sorted(natural_cmp, &["img12.png", "img10.png", "IMG2.png"]),
+            ["IMG2.png", "img10.png", "img12.png"]
+        );
+        // Only the case differs, so the bytes decide
+        assert_eq!(
+            natural_cmp(OsStr::new("A"), OsStr::new("a")),
+            Ordering::Less
+        );
+    }
+}
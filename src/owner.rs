@@ -0,0 +1,56 @@
+//! User and group name lookup.
+//!
+//! Lookups go through a per-thread cache, so a listing only resolves each
+//! distinct uid or gid once.
+
+#[cfg(unix)]
+use uzers::{Groups, Users, UsersCache};
+
+#[cfg(unix)]
+thread_local! {
+    static CACHE: UsersCache = UsersCache::new();
+}
+
+/// Returns the name of the user `uid`, or the number itself if unknown.
+#[cfg(unix)]
+pub(crate) fn user_name(uid: u32) -> String {
+    CACHE
+        .with(|cache| cache.get_user_by_uid(uid))
+        .map(|user| user.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+/// Returns the name of the group `gid`, or the number itself if unknown.
+#[cfg(unix)]
+pub(crate) fn group_name(gid: u32) -> String {
+    CACHE
+        .with(|cache| cache.get_group_by_gid(gid))
+        .map(|group| group.name().to_string_lossy().into_owned())
+        .unwrap_or_else(|| gid.to_string())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn user_name(uid: u32) -> String {
+    uid.to_string()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn group_name(gid: u32) -> String {
+    gid.to_string()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_resolves() {
+        assert_eq!(user_name(0), "root");
+    }
+
+    #[test]
+    fn test_unknown_id_falls_back_to_number() {
+        assert_eq!(user_name(4_000_000_000), "4000000000");
+        assert_eq!(group_name(4_000_000_000), "4000000000");
+    }
+}
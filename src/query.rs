@@ -0,0 +1,935 @@
+//! The `--where` query language.
+//!
+//! A query is an expression such as
+//! `size > 10M && ext == "log" && mtime < now - 7d`, parsed and type-checked
+//! once and then tested against each entry.
+
+use crate::{
+    entry::{Entry, FileType, TimeField},
+    format::{parse_age, parse_size, parse_time},
+    owner::{group_name, user_name},
+    width::display_width,
+};
+use regex::Regex;
+use std::{cmp::Ordering, fmt, path::Path, str::FromStr, time::SystemTime};
+
+/// A type-checked query that entries can be tested against.
+///
+/// ```
+/// use zls::Query;
+///
+/// let query: Query = r#"size > 10M && ext == "log" && mtime < now - 7d"#.parse()?;
+/// # Ok::<(), zls::QueryError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    /// Parses `source`, with `now` standing for the current time.
+    pub fn parse(source: &str) -> Result<Self, QueryError> {
+        Self::parse_at(source, SystemTime::now())
+    }
+
+    fn parse_at(source: &str, now: SystemTime) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            position: 0,
+            now,
+        };
+        let typed = parser.parse_or()?;
+        let (token, offset) = parser.peek();
+        if *token != Token::End {
+            return Err(parser.error(offset, format!("unexpected {}", token)));
+        }
+        if typed.ty != Type::Bool {
+            return Err(parser.error(
+                typed.start,
+                format!(
+                    "the query must be true or false, but this is {}",
+                    typed.ty.name()
+                ),
+            ));
+        }
+        Ok(Query { expr: typed.expr })
+    }
+
+    /// Whether `entry`, found at `relative` below the listed directory,
+    /// matches.
+    pub fn matches(&self, entry: &Entry, relative: &Path) -> bool {
+        matches!(self.expr.eval(entry, relative), Value::Bool(true))
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+/// Why a query couldn't be parsed, and where.
+///
+/// Displays the message followed by the query with a caret under the
+/// problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+    /// The byte offset in the query where the problem was found.
+    pub offset: usize,
+    source: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = display_width(&self.source[..self.offset]);
+        write!(f, "{}\n  {}\n  {:column$}^", self.message, self.source, "")
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Every field a query can refer to.
+const FIELDS: &[(&str, Field)] = &[
+    ("name", Field::Name),
+    ("ext", Field::Extension),
+    ("path", Field::Path),
+    ("type", Field::Type),
+    ("size", Field::Size),
+    ("blocks", Field::Blocks),
+    ("links", Field::Links),
+    ("uid", Field::Uid),
+    ("gid", Field::Gid),
+    ("user", Field::User),
+    ("group", Field::Group),
+    ("mtime", Field::Time(TimeField::Modification)),
+    ("atime", Field::Time(TimeField::Access)),
+    ("ctime", Field::Time(TimeField::Change)),
+    ("btime", Field::Time(TimeField::Birth)),
+    ("hidden", Field::Hidden),
+    ("broken", Field::Broken),
+];
+
+/// The file types `type` can be compared with, and their short forms.
+const FILE_TYPES: &[(&str, FileType)] = &[
+    ("f", FileType::File),
+    ("d", FileType::Directory),
+    ("l", FileType::Symlink),
+    ("p", FileType::Fifo),
+    ("s", FileType::Socket),
+    ("b", FileType::BlockDevice),
+    ("c", FileType::CharDevice),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Name,
+    Extension,
+    Path,
+    Type,
+    Size,
+    Blocks,
+    Links,
+    Uid,
+    Gid,
+    User,
+    Group,
+    Time(TimeField),
+    Hidden,
+    Broken,
+}
+
+impl Field {
+    fn ty(self) -> Type {
+        match self {
+            Field::Name
+            | Field::Extension
+            | Field::Path
+            | Field::Type
+            | Field::User
+            | Field::Group => Type::String,
+            Field::Size | Field::Blocks | Field::Links | Field::Uid | Field::Gid => Type::Number,
+            Field::Time(_) => Type::Time,
+            Field::Hidden | Field::Broken => Type::Bool,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Bool,
+    Number,
+    String,
+    Time,
+    Duration,
+}
+
+impl Type {
+    fn name(self) -> &'static str {
+        match self {
+            Type::Bool => "true or false",
+            Type::Number => "a number",
+            Type::String => "a string",
+            Type::Time => "a time",
+            Type::Duration => "a duration",
+        }
+    }
+}
+
+/// A value while evaluating. Times are seconds since the Unix epoch and
+/// durations are seconds.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Time(f64),
+    Duration(f64),
+    /// A time the entry doesn't have, which never compares as true.
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering.is_eq(),
+            CompareOp::Ne => ordering.is_ne(),
+            CompareOp::Lt => ordering.is_lt(),
+            CompareOp::Le => ordering.is_le(),
+            CompareOp::Gt => ordering.is_gt(),
+            CompareOp::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Field(Field),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    /// A regex search, negated for `!~`.
+    Matches(Box<Expr>, Regex, bool),
+    Add(Box<Expr>, Box<Expr>),
+    Subtract(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, entry: &Entry, relative: &Path) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Field(field) => field_value(*field, entry, relative),
+            Expr::Not(expr) => Value::Bool(!expr.eval(entry, relative).is_true()),
+            Expr::And(left, right) => Value::Bool(
+                left.eval(entry, relative).is_true() && right.eval(entry, relative).is_true(),
+            ),
+            Expr::Or(left, right) => Value::Bool(
+                left.eval(entry, relative).is_true() || right.eval(entry, relative).is_true(),
+            ),
+            Expr::Compare(left, op, right) => {
+                let ordering = match (left.eval(entry, relative), right.eval(entry, relative)) {
+                    (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(&b)),
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(&b)),
+                    (Value::Number(a), Value::Number(b))
+                    | (Value::Time(a), Value::Time(b))
+                    | (Value::Duration(a), Value::Duration(b)) => a.partial_cmp(&b),
+                    _ => None,
+                };
+                Value::Bool(ordering.is_some_and(|ordering| op.holds(ordering)))
+            }
+            Expr::Matches(expr, regex, negated) => match expr.eval(entry, relative) {
+                Value::String(text) => Value::Bool(regex.is_match(&text) != *negated),
+                _ => Value::Bool(false),
+            },
+            Expr::Add(left, right) => {
+                match (left.eval(entry, relative), right.eval(entry, relative)) {
+                    (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                    (Value::Duration(a), Value::Duration(b)) => Value::Duration(a + b),
+                    (Value::Time(a), Value::Duration(b)) | (Value::Duration(b), Value::Time(a)) => {
+                        Value::Time(a + b)
+                    }
+                    _ => Value::Missing,
+                }
+            }
+            Expr::Subtract(left, right) => {
+                match (left.eval(entry, relative), right.eval(entry, relative)) {
+                    (Value::Number(a), Value::Number(b)) => Value::Number(a - b),
+                    (Value::Duration(a), Value::Duration(b)) => Value::Duration(a - b),
+                    (Value::Time(a), Value::Duration(b)) => Value::Time(a - b),
+                    (Value::Time(a), Value::Time(b)) => Value::Duration(a - b),
+                    _ => Value::Missing,
+                }
+            }
+        }
+    }
+}
+
+impl Value {
+    fn is_true(&self) -> bool {
+        *self == Value::Bool(true)
+    }
+}
+
+fn field_value(field: Field, entry: &Entry, relative: &Path) -> Value {
+    match field {
+        Field::Name => Value::String(entry.name.to_string_lossy().into_owned()),
+        Field::Extension => Value::String(
+            Path::new(&entry.name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+        ),
+        Field::Path => Value::String(relative.to_string_lossy().into_owned()),
+        Field::Type => Value::String(entry.file_type().as_str().to_string()),
+        Field::Size => Value::Number(entry.size as f64),
+        Field::Blocks => Value::Number(entry.blocks as f64),
+        Field::Links => Value::Number(entry.nlink as f64),
+        Field::Uid => Value::Number(entry.uid as f64),
+        Field::Gid => Value::Number(entry.gid as f64),
+        Field::User => Value::String(user_name(entry.uid)),
+        Field::Group => Value::String(group_name(entry.gid)),
+        Field::Time(field) => entry
+            .time(field)
+            .map_or(Value::Missing, |time| Value::Time(seconds(time))),
+        Field::Hidden => Value::Bool(entry.is_hidden),
+        Field::Broken => Value::Bool(entry.is_broken),
+    }
+}
+
+/// Seconds since the Unix epoch, negative before it.
+fn seconds(time: SystemTime) -> f64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs_f64(),
+        Err(error) => -error.duration().as_secs_f64(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    String(String),
+    Ident(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(text) | Token::Ident(text) => write!(f, "'{}'", text),
+            Token::String(text) => write!(f, "{:?}", text),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::End => f.write_str("end of query"),
+        }
+    }
+}
+
+/// Symbols, longest first so `<=` isn't read as `<` then `=`.
+const SYMBOLS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "=~", "!~", "<", ">", "!", "+", "-", "(", ")",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let error = |offset: usize, message: String| QueryError {
+        message,
+        offset,
+        source: source.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '.') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Token::Number(source[start..end].to_string()), start));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push((Token::Ident(source[start..end].to_string()), start));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(error(start, "unterminated string".to_string())),
+                    Some((_, quote)) if quote == c => break,
+                    // A backslash only escapes the quote, so "\.log$" reaches a regex as written
+                    Some((_, '\\')) => match chars.peek() {
+                        Some(&(_, quote)) if quote == c => {
+                            text.push(quote);
+                            chars.next();
+                        }
+                        _ => text.push('\\'),
+                    },
+                    Some((_, c)) => text.push(c),
+                }
+            }
+            tokens.push((Token::String(text), start));
+        } else if let Some(symbol) = SYMBOLS
+            .iter()
+            .find(|symbol| source[start..].starts_with(**symbol))
+        {
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push((Token::Symbol(symbol), start));
+        } else if c == '=' || c == '&' || c == '|' {
+            let hint = match c {
+                '=' => "use '==' to compare",
+                '&' => "use '&&' for 'and'",
+                _ => "use '||' for 'or'",
+            };
+            return Err(error(start, format!("unexpected '{}' ({})", c, hint)));
+        } else {
+            return Err(error(start, format!("unexpected '{}'", c)));
+        }
+    }
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+/// An expression along with its type and where it starts.
+struct Typed {
+    expr: Expr,
+    ty: Type,
+    start: usize,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    now: SystemTime,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> (&Token, usize) {
+        let (token, offset) = &self.tokens[self.position];
+        (token, *offset)
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.position].clone();
+        if token.0 != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Consumes the next token if it's one of `symbols` or the keyword that
+    /// spells it.
+    fn eat(&mut self, symbols: &[&'static str]) -> Option<(&'static str, usize)> {
+        let (token, offset) = self.peek();
+        let symbol = match token {
+            Token::Symbol(symbol) => Some(*symbol),
+            Token::Ident(word) => match word.as_str() {
+                "and" => Some("&&"),
+                "or" => Some("||"),
+                "not" => Some("!"),
+                _ => None,
+            },
+            _ => None,
+        }
+        .filter(|symbol| symbols.contains(symbol))?;
+        self.advance();
+        Some((symbol, offset))
+    }
+
+    fn error(&self, offset: usize, message: String) -> QueryError {
+        QueryError {
+            message,
+            offset,
+            source: self.source.to_string(),
+        }
+    }
+
+    fn expect_bool(&self, typed: &Typed, symbol: &str) -> Result<(), QueryError> {
+        if typed.ty == Type::Bool {
+            Ok(())
+        } else {
+            Err(self.error(
+                typed.start,
+                format!(
+                    "'{}' needs true or false on both sides, but this is {}",
+                    symbol,
+                    typed.ty.name()
+                ),
+            ))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Typed, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.eat(&["||"]).is_some() {
+            let right = self.parse_and()?;
+            self.expect_bool(&left, "||")?;
+            self.expect_bool(&right, "||")?;
+            left = Typed {
+                expr: Expr::Or(Box::new(left.expr), Box::new(right.expr)),
+                ty: Type::Bool,
+                start: left.start,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Typed, QueryError> {
+        let mut left = self.parse_not()?;
+        while self.eat(&["&&"]).is_some() {
+            let right = self.parse_not()?;
+            self.expect_bool(&left, "&&")?;
+            self.expect_bool(&right, "&&")?;
+            left = Typed {
+                expr: Expr::And(Box::new(left.expr), Box::new(right.expr)),
+                ty: Type::Bool,
+                start: left.start,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Typed, QueryError> {
+        let Some((_, start)) = self.eat(&["!"]) else {
+            return self.parse_comparison();
+        };
+        let operand = self.parse_not()?;
+        self.expect_bool(&operand, "!")?;
+        Ok(Typed {
+            expr: Expr::Not(Box::new(operand.expr)),
+            ty: Type::Bool,
+            start,
+        })
+    }
+
+    fn parse_comparison(&mut self) -> Result<Typed, QueryError> {
+        let left = self.parse_sum()?;
+        let Some((symbol, offset)) = self.eat(&["==", "!=", "<", "<=", ">", ">=", "=~", "!~"])
+        else {
+            return Ok(left);
+        };
+        let right = self.parse_sum()?;
+
+        if symbol == "=~" || symbol == "!~" {
+            return self.regex_match(left, symbol, right);
+        }
+        // A literal may be on either side, as in `"2024-01-31" < mtime`
+        let right = self.coerce(&left, right)?;
+        let left = self.coerce(&right, left)?;
+        if left.ty != right.ty {
+            return Err(self.error(
+                offset,
+                format!("cannot compare {} with {}", left.ty.name(), right.ty.name()),
+            ));
+        }
+        if left.ty == Type::Bool && symbol != "==" && symbol != "!=" {
+            return Err(self.error(
+                offset,
+                format!(
+                    "true and false can only be compared with '==' or '!=', not '{}'",
+                    symbol
+                ),
+            ));
+        }
+
+        let op = match symbol {
+            "==" => CompareOp::Eq,
+            "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            _ => CompareOp::Ge,
+        };
+        Ok(Typed {
+            expr: Expr::Compare(Box::new(left.expr), op, Box::new(right.expr)),
+            ty: Type::Bool,
+            start: left.start,
+        })
+    }
+
+    /// Turns a string literal compared with `other` into a time or a size
+    /// when `other` is one, and checks one compared with `type` names a file
+    /// type. Anything else is returned unchanged.
+    fn coerce(&self, other: &Typed, literal: Typed) -> Result<Typed, QueryError> {
+        let Expr::Literal(Value::String(text)) = &literal.expr else {
+            return Ok(literal);
+        };
+        match (&other.expr, other.ty) {
+            (_, Type::Time) => {
+                let time = parse_time(text, self.now)
+                    .map_err(|message| self.error(literal.start, message))?;
+                Ok(Typed {
+                    expr: Expr::Literal(Value::Time(seconds(time))),
+                    ty: Type::Time,
+                    start: literal.start,
+                })
+            }
+            (_, Type::Number) => {
+                let size =
+                    parse_size(text).map_err(|message| self.error(literal.start, message))?;
+                Ok(Typed {
+                    expr: Expr::Literal(Value::Number(size as f64)),
+                    ty: Type::Number,
+                    start: literal.start,
+                })
+            }
+            (Expr::Field(Field::Type), _) => {
+                let file_type = FILE_TYPES
+                    .iter()
+                    .find(|(short, file_type)| text == short || text == file_type.as_str())
+                    .map(|(_, file_type)| file_type.as_str())
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = FILE_TYPES
+                            .iter()
+                            .map(|(_, file_type)| file_type.as_str())
+                            .collect();
+                        self.error(
+                            literal.start,
+                            format!(
+                                "unknown file type '{}' (expected {} or their first letters)",
+                                text,
+                                names.join(", ")
+                            ),
+                        )
+                    })?;
+                Ok(Typed {
+                    expr: Expr::Literal(Value::String(file_type.to_string())),
+                    ..literal
+                })
+            }
+            // Extensions are compared lowercased, so `ext == "LOG"` matches
+            // `app.log` and `app.LOG` alike
+            (Expr::Field(Field::Extension), _) => Ok(Typed {
+                expr: Expr::Literal(Value::String(text.to_lowercase())),
+                ..literal
+            }),
+            _ => Ok(literal),
+        }
+    }
+
+    fn regex_match(&self, left: Typed, symbol: &str, right: Typed) -> Result<Typed, QueryError> {
+        if left.ty != Type::String {
+            return Err(self.error(
+                left.start,
+                format!(
+                    "'{}' needs a string on the left, but this is {}",
+                    symbol,
+                    left.ty.name()
+                ),
+            ));
+        }
+        let Expr::Literal(Value::String(pattern)) = &right.expr else {
+            return Err(self.error(
+                right.start,
+                format!(
+                    "'{}' needs a quoted regular expression on the right",
+                    symbol
+                ),
+            ));
+        };
+        let regex = Regex::new(pattern).map_err(|error| {
+            self.error(
+                right.start,
+                format!("invalid regular expression: {}", error),
+            )
+        })?;
+        Ok(Typed {
+            expr: Expr::Matches(Box::new(left.expr), regex, symbol == "!~"),
+            ty: Type::Bool,
+            start: left.start,
+        })
+    }
+
+    fn parse_sum(&mut self) -> Result<Typed, QueryError> {
+        let mut left = self.parse_atom()?;
+        while let Some((symbol, offset)) = self.eat(&["+", "-"]) {
+            let right = self.parse_atom()?;
+            let ty = match (left.ty, symbol, right.ty) {
+                (Type::Number, _, Type::Number) => Type::Number,
+                (Type::Duration, _, Type::Duration) => Type::Duration,
+                (Type::Time, _, Type::Duration) => Type::Time,
+                (Type::Duration, "+", Type::Time) => Type::Time,
+                (Type::Time, "-", Type::Time) => Type::Duration,
+                _ => {
+                    let verb = if symbol == "+" { "add" } else { "subtract" };
+                    let (first, second) = if symbol == "+" {
+                        (left.ty, right.ty)
+                    } else {
+                        (right.ty, left.ty)
+                    };
+                    let preposition = if symbol == "+" { "to" } else { "from" };
+                    return Err(self.error(
+                        offset,
+                        format!(
+                            "cannot {} {} {} {}",
+                            verb,
+                            first.name(),
+                            preposition,
+                            second.name()
+                        ),
+                    ));
+                }
+            };
+            let (left_expr, right_expr) = (Box::new(left.expr), Box::new(right.expr));
+            left = Typed {
+                expr: if symbol == "+" {
+                    Expr::Add(left_expr, right_expr)
+                } else {
+                    Expr::Subtract(left_expr, right_expr)
+                },
+                ty,
+                start: left.start,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_atom(&mut self) -> Result<Typed, QueryError> {
+        let (token, start) = self.advance();
+        let (expr, ty) = match token {
+            Token::Symbol("(") => {
+                let inner = self.parse_or()?;
+                let (token, offset) = self.advance();
+                if token != Token::Symbol(")") {
+                    return Err(self.error(offset, format!("expected ')', found {}", token)));
+                }
+                return Ok(Typed { start, ..inner });
+            }
+            Token::Number(text) => {
+                if let Some(age) = parse_age(&text) {
+                    (
+                        Expr::Literal(Value::Duration(age.as_secs_f64())),
+                        Type::Duration,
+                    )
+                } else {
+                    let size = parse_size(&text).map_err(|_| {
+                        self.error(
+                            start,
+                            format!(
+                                "invalid number '{}' (expected a size such as 10M or a duration such as 7d)",
+                                text
+                            ),
+                        )
+                    })?;
+                    (Expr::Literal(Value::Number(size as f64)), Type::Number)
+                }
+            }
+            Token::String(text) => (Expr::Literal(Value::String(text)), Type::String),
+            Token::Ident(word) => match word.as_str() {
+                "true" => (Expr::Literal(Value::Bool(true)), Type::Bool),
+                "false" => (Expr::Literal(Value::Bool(false)), Type::Bool),
+                "now" => (Expr::Literal(Value::Time(seconds(self.now))), Type::Time),
+                _ => {
+                    let field = FIELDS
+                        .iter()
+                        .find(|(name, _)| *name == word)
+                        .map(|(_, field)| *field)
+                        .ok_or_else(|| {
+                            let names: Vec<&str> = FIELDS.iter().map(|(name, _)| *name).collect();
+                            self.error(
+                                start,
+                                format!(
+                                    "unknown field '{}' (expected one of {}, or now)",
+                                    word,
+                                    names.join(", ")
+                                ),
+                            )
+                        })?;
+                    (Expr::Field(field), field.ty())
+                }
+            },
+            token => {
+                return Err(self.error(start, format!("expected a value, found {}", token)));
+            }
+        };
+        Ok(Typed { expr, ty, start })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const DAY: Duration = Duration::from_secs(86400);
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + 1000 * DAY
+    }
+
+    fn entry(name: &str, size: u64, age: Duration) -> Entry {
+        Entry {
+            size,
+            blocks: size.div_ceil(512),
+            modified: Some(now() - age),
+            ..Entry::test_file(name)
+        }
+    }
+
+    fn matching(query: &str, entries: &[Entry]) -> Vec<String> {
+        let query = Query::parse_at(query, now()).unwrap();
+        entries
+            .iter()
+            .filter(|entry| query.matches(entry, &entry.path))
+            .map(|entry| entry.name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn error(query: &str) -> QueryError {
+        Query::parse_at(query, now()).unwrap_err()
+    }
+
+    #[test]
+    fn test_query_combines_conditions() {
+        let entries = [
+            entry("old.log", 20 << 20, 30 * DAY),
+            entry("new.log", 20 << 20, DAY),
+            entry("small.LOG", 10, 30 * DAY),
+            entry("old.txt", 20 << 20, 30 * DAY),
+        ];
+        assert_eq!(
+            matching(
+                r#"size > 10M && ext == "log" && mtime < now - 7d"#,
+                &entries
+            ),
+            ["old.log"]
+        );
+        assert_eq!(
+            matching(
+                r#"ext == "log" and not (size >= 1K or mtime > now - 2w)"#,
+                &entries
+            ),
+            ["small.LOG"]
+        );
+        assert_eq!(
+            matching(r#"name =~ "^old" || name !~ "\.log$""#, &entries),
+            ["old.log", "small.LOG", "old.txt"]
+        );
+        assert_eq!(
+            matching("now - mtime > 1w && size == 10", &entries),
+            ["small.LOG"]
+        );
+    }
+
+    #[test]
+    fn test_query_coerces_literals() {
+        let entries = [entry("a", 0, Duration::ZERO), entry("b", 0, 900 * DAY)];
+        // The entries are from 1972-09-27 and 1970-04-11
+        assert_eq!(matching(r#"mtime < "1971-01-01""#, &entries), ["b"]);
+        assert_eq!(matching(r#""1971-01-01" < mtime"#, &entries), ["a"]);
+        assert_eq!(
+            matching(r#""d" != type && "1K" > size"#, &entries),
+            ["a", "b"]
+        );
+        assert_eq!(matching(r#"type == "f" && !hidden"#, &entries), ["a", "b"]);
+        assert_eq!(
+            matching(r#"type == "directory""#, &entries),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_query_extension_ignores_case() {
+        let entries = [
+            entry("app.LOG", 0, DAY),
+            entry("b.log", 0, DAY),
+            entry("c.txt", 0, DAY),
+        ];
+        assert_eq!(matching(r#"ext == "LOG""#, &entries), ["app.LOG", "b.log"]);
+        assert_eq!(matching(r#""Log" != ext"#, &entries), ["c.txt"]);
+    }
+
+    #[test]
+    fn test_query_missing_times_never_match() {
+        let entries = [entry("a", 0, DAY)];
+        assert!(matching("atime < now", &entries).is_empty());
+        assert!(matching("atime >= now", &entries).is_empty());
+        assert_eq!(matching("!(atime < now)", &entries), ["a"]);
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let err = error("size > 10M && ext == log");
+        assert_eq!(err.offset, 21);
+        assert!(err.message.starts_with("unknown field 'log'"));
+        assert_eq!(
+            err.to_string().lines().skip(1).collect::<Vec<_>>(),
+            ["  size > 10M && ext == log", "                       ^"]
+        );
+
+        assert_eq!(
+            error(r#"size > hidden"#).message,
+            "cannot compare a number with true or false"
+        );
+        assert_eq!(
+            error(r#"name == size"#).message,
+            "cannot compare a string with a number"
+        );
+        let err = error(r#""big" < size"#);
+        assert!(err.message.starts_with("invalid size 'big'"));
+        assert_eq!(err.offset, 0);
+        assert_eq!(
+            error("size = 1").message,
+            "unexpected '=' (use '==' to compare)"
+        );
+        assert_eq!(
+            error("size > 10M &&").message,
+            "expected a value, found end of query"
+        );
+        assert_eq!(
+            error("size && hidden").message,
+            "'&&' needs true or false on both sides, but this is a number"
+        );
+        assert_eq!(
+            error("mtime + mtime > now").message,
+            "cannot add a time to a time"
+        );
+        assert_eq!(
+            error("size").message,
+            "the query must be true or false, but this is a number"
+        );
+        assert!(error(r#"type == "x""#)
+            .message
+            .starts_with("unknown file type 'x'"));
+        assert!(error(r#"mtime < "soon""#)
+            .message
+            .starts_with("invalid time 'soon'"));
+        assert!(error("size > 10X")
+            .message
+            .starts_with("invalid number '10X'"));
+        assert!(error(r#"name =~ "(""#)
+            .message
+            .starts_with("invalid regular expression"));
+        assert_eq!(error("(hidden").message, "expected ')', found end of query");
+        assert_eq!(error("hidden)").message, "unexpected ')'");
+        assert_eq!(error(r#"name == "a"#).message, "unterminated string");
+    }
+}
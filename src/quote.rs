@@ -0,0 +1,278 @@
+//! Quoting of file names for display, following GNU `ls --quoting-style`,
+//! and hiding of characters that would let a name control the terminal.
+
+use std::{borrow::Cow, ffi::OsStr, fmt::Write};
+
+/// How file names are quoted in the human-readable layouts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuotingStyle {
+    /// Names as they are, with bytes that aren't valid UTF-8 shown as U+FFFD.
+    #[default]
+    Literal,
+    /// Quoted for a POSIX shell when they contain special characters.
+    Shell,
+    /// Like [`QuotingStyle::Shell`], but with control characters and invalid
+    /// bytes written as `$'\n'` style escapes, so every name can be pasted
+    /// back into a shell.
+    ShellEscape,
+    /// In double quotes, with C escapes such as `\n` and `\377`.
+    C,
+    /// C escapes without the quotes, also escaping spaces.
+    Escape,
+}
+
+/// Characters that never need quoting for a shell.
+const SHELL_SAFE: &str = "%+,-./:=@_^";
+/// Characters that still have a meaning inside double quotes.
+const DOUBLE_QUOTE_SPECIAL: &str = "\"$`\\!";
+
+/// Whether `c` could change how a terminal shows the text around it.
+///
+/// Besides control characters such as newlines and the escape that starts
+/// terminal sequences, this covers the bidirectional overrides that can make
+/// a name read differently from how it's stored.
+pub(crate) fn is_unsafe_char(c: char) -> bool {
+    c.is_control() || matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Replaces each character for which [`is_unsafe_char`] holds with `?`, like
+/// `ls -q`.
+pub(crate) fn hide_control_chars(text: &str) -> Cow<'_, str> {
+    if text.chars().any(is_unsafe_char) {
+        Cow::Owned(
+            text.chars()
+                .map(|c| if is_unsafe_char(c) { '?' } else { c })
+                .collect(),
+        )
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// A run of a name that is either valid text or bytes that aren't.
+enum Chunk<'a> {
+    Text(&'a str),
+    Bytes(&'a [u8]),
+}
+
+/// Formats `name` for display in the given style.
+pub(crate) fn quote(name: &OsStr, style: QuotingStyle) -> String {
+    match style {
+        QuotingStyle::Literal => name.to_string_lossy().into_owned(),
+        QuotingStyle::Shell => quote_shell(name, false),
+        QuotingStyle::ShellEscape => quote_shell(name, true),
+        QuotingStyle::C => format!("\"{}\"", escape_c(name, false)),
+        QuotingStyle::Escape => escape_c(name, true),
+    }
+}
+
+/// Splits a name into valid UTF-8 text and the bytes in between.
+fn chunks(name: &OsStr) -> impl Iterator<Item = Chunk<'_>> {
+    name.as_encoded_bytes().utf8_chunks().flat_map(|chunk| {
+        let invalid = chunk.invalid();
+        [
+            Some(Chunk::Text(chunk.valid())).filter(|_| !chunk.valid().is_empty()),
+            Some(Chunk::Bytes(invalid)).filter(|_| !invalid.is_empty()),
+        ]
+        .into_iter()
+        .flatten()
+    })
+}
+
+fn is_shell_safe(c: char, first: bool) -> bool {
+    if c.is_ascii() {
+        c.is_ascii_alphanumeric() || SHELL_SAFE.contains(c) || (!first && "~#".contains(c))
+    } else {
+        !c.is_control() && !c.is_whitespace()
+    }
+}
+
+fn quote_shell(name: &OsStr, escape: bool) -> String {
+    let text = name.to_string_lossy();
+    let needs_escapes = escape
+        && chunks(name).any(|chunk| match chunk {
+            Chunk::Text(text) => text.chars().any(char::is_control),
+            Chunk::Bytes(_) => true,
+        });
+    let needs_quotes = text.is_empty()
+        || text
+            .chars()
+            .enumerate()
+            .any(|(i, c)| !is_shell_safe(c, i == 0));
+
+    if needs_escapes {
+        return quote_shell_escaped(name);
+    } else if !needs_quotes {
+        return text.into_owned();
+    }
+
+    // Prefer "don't" over 'don'\''t' when double quotes are safe
+    if text.contains('\'') && !text.chars().any(|c| DOUBLE_QUOTE_SPECIAL.contains(c)) {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
+
+/// Quotes printable runs in `'...'` and writes everything else as `$'...'`.
+fn quote_shell_escaped(name: &OsStr) -> String {
+    let mut out = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut open = |out: &mut String, want_escape: bool| {
+        if want_escape && !escaped {
+            out.push_str(if quoted { "'$'" } else { "$'" });
+        } else if !want_escape && !quoted {
+            out.push_str(if escaped { "''" } else { "'" });
+        }
+        quoted = !want_escape;
+        escaped = want_escape;
+    };
+
+    for chunk in chunks(name) {
+        match chunk {
+            Chunk::Text(text) => {
+                for c in text.chars() {
+                    if c.is_control() {
+                        open(&mut out, true);
+                        push_c_escape(&mut out, c);
+                    } else {
+                        open(&mut out, false);
+                        if c == '\'' {
+                            out.push_str("'\\''");
+                        } else {
+                            out.push(c);
+                        }
+                    }
+                }
+            }
+            Chunk::Bytes(bytes) => {
+                open(&mut out, true);
+                for byte in bytes {
+                    let _ = write!(out, "\\{:03o}", byte);
+                }
+            }
+        }
+    }
+    out.push('\'');
+    out
+}
+
+fn escape_c(name: &OsStr, escape_spaces: bool) -> String {
+    let mut out = String::new();
+    for chunk in chunks(name) {
+        match chunk {
+            Chunk::Text(text) => {
+                for c in text.chars() {
+                    match c {
+                        '"' if !escape_spaces => out.push_str("\\\""),
+                        ' ' if escape_spaces => out.push_str("\\ "),
+                        '\\' => out.push_str("\\\\"),
+                        c if c.is_control() => push_c_escape(&mut out, c),
+                        c => out.push(c),
+                    }
+                }
+            }
+            Chunk::Bytes(bytes) => {
+                for byte in bytes {
+                    let _ = write!(out, "\\{:03o}", byte);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Writes a control character as a C escape, using octal for the ones
+/// without a short form.
+fn push_c_escape(out: &mut String, c: char) {
+    match c {
+        '\x07' => out.push_str("\\a"),
+        '\x08' => out.push_str("\\b"),
+        '\x0c' => out.push_str("\\f"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        '\x0b' => out.push_str("\\v"),
+        c => {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                let _ = write!(out, "\\{:03o}", byte);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quoted(name: &str, style: QuotingStyle) -> String {
+        quote(OsStr::new(name), style)
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        assert_eq!(quoted("a b", QuotingStyle::Literal), "a b");
+        assert_eq!(quoted("a\nb", QuotingStyle::Literal), "a\nb");
+    }
+
+    #[test]
+    fn test_quote_shell() {
+        assert_eq!(quoted("main.rs", QuotingStyle::Shell), "main.rs");
+        assert_eq!(quoted("日本語.txt", QuotingStyle::Shell), "日本語.txt");
+        assert_eq!(quoted("a b", QuotingStyle::Shell), "'a b'");
+        assert_eq!(quoted("$HOME", QuotingStyle::Shell), "'$HOME'");
+        assert_eq!(quoted("~user", QuotingStyle::Shell), "'~user'");
+        assert_eq!(quoted("a~b", QuotingStyle::Shell), "a~b");
+        assert_eq!(quoted("don't", QuotingStyle::Shell), "\"don't\"");
+        assert_eq!(quoted("it's $5", QuotingStyle::Shell), "'it'\\''s $5'");
+        assert_eq!(quoted("", QuotingStyle::Shell), "''");
+    }
+
+    #[test]
+    fn test_quote_shell_escape() {
+        assert_eq!(quoted("a b", QuotingStyle::ShellEscape), "'a b'");
+        assert_eq!(quoted("a\nb", QuotingStyle::ShellEscape), "'a'$'\\n''b'");
+        assert_eq!(quoted("\tx", QuotingStyle::ShellEscape), "$'\\t''x'");
+        assert_eq!(quoted("x\x1b", QuotingStyle::ShellEscape), "'x'$'\\033'");
+    }
+
+    #[test]
+    fn test_quote_c_and_escape() {
+        assert_eq!(quoted("a b", QuotingStyle::C), "\"a b\"");
+        assert_eq!(quoted("say \"hi\"", QuotingStyle::C), "\"say \\\"hi\\\"\"");
+        assert_eq!(quoted("a\\b\n", QuotingStyle::C), "\"a\\\\b\\n\"");
+        assert_eq!(quoted("a b\x01", QuotingStyle::Escape), "a\\ b\\001");
+    }
+
+    #[test]
+    fn test_hide_control_chars() {
+        assert_eq!(hide_control_chars("main.rs"), "main.rs");
+        assert_eq!(hide_control_chars("a\nb\tc"), "a?b?c");
+        // An OSC 8 hyperlink and a cursor movement
+        assert_eq!(
+            hide_control_chars("\x1b]8;;http://x\x07evil\x1b[2A"),
+            "?]8;;http://x?evil?[2A"
+        );
+        // C1 controls and the DEL character
+        assert_eq!(hide_control_chars("a\u{9b}31mb\x7f"), "a?31mb?");
+        // A right-to-left override that disguises "exe.txt" as "txt.exe"
+        assert_eq!(hide_control_chars("\u{202e}txt.exe"), "?txt.exe");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_quote_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        assert_eq!(quote(name, QuotingStyle::Literal), "caf\u{fffd}.txt");
+        assert_eq!(
+            quote(name, QuotingStyle::ShellEscape),
+            "'caf'$'\\351''.txt'"
+        );
+        assert_eq!(quote(name, QuotingStyle::C), "\"caf\\351.txt\"");
+        assert_eq!(quote(name, QuotingStyle::Escape), "caf\\351.txt");
+    }
+}
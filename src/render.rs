@@ -0,0 +1,814 @@
+use crate::{
+    colors::LsColors,
+    csv::{self, Column},
+    entry::{Entry, TimeField},
+    format::{format_mode, format_size, TimeStyle},
+    json,
+    lister::{GroupBy, Listing, TreeNode},
+    owner::{group_name, user_name},
+    quote::{hide_control_chars, quote, QuotingStyle},
+    width::{display_width, truncate},
+};
+use std::{
+    ffi::OsStr,
+    io::{self, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+/// How a listing is laid out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// One entry per line with permissions, owner, size and modification time.
+    #[default]
+    Long,
+    /// Names in a grid that fits the terminal width, ordered down columns.
+    Short,
+    /// Names in a grid that fits the terminal width, ordered across rows.
+    Across,
+    /// One name per line.
+    SingleColumn,
+    /// A single JSON array of entries.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Tab-separated values with a header row.
+    Tsv,
+}
+
+impl Layout {
+    /// Whether the layout is meant for other programs rather than people.
+    ///
+    /// These layouts never use color and write recursive listings as a single
+    /// sequence of entries rather than one block per directory.
+    pub fn is_machine_readable(self) -> bool {
+        !matches!(
+            self,
+            Layout::Long | Layout::Short | Layout::Across | Layout::SingleColumn
+        )
+    }
+}
+
+/// Options controlling how entries are rendered.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub layout: Layout,
+    pub human: bool,
+    /// Show numeric user and group ids instead of names.
+    pub numeric_ids: bool,
+    pub term_width: usize,
+    /// The columns written by the CSV and TSV layouts.
+    pub columns: Vec<Column>,
+    /// Colors for entry names in the human-readable layouts, or `None` to
+    /// write plain text.
+    pub colors: Option<LsColors>,
+    /// Truncate names wider than this many columns in the human-readable
+    /// layouts, ending them with an ellipsis.
+    pub max_name_width: Option<usize>,
+    /// Which timestamp the long layout shows.
+    pub time_field: TimeField,
+    /// How times are shown in the long layout.
+    pub time_style: TimeStyle,
+    /// How names and paths are quoted in the human-readable layouts.
+    pub quoting: QuotingStyle,
+    /// Replace control characters in names with `?` so they can't move the
+    /// cursor or inject terminal escapes, like `ls -q`.
+    ///
+    /// JSON output escapes them instead, so it is unaffected.
+    pub hide_control_chars: bool,
+    /// Write a heading above each group of entries in the long layout, for
+    /// listings grouped with [`crate::Lister::group_by`].
+    pub group_headers: GroupBy,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            layout: Layout::default(),
+            human: true,
+            numeric_ids: false,
+            term_width: 80,
+            columns: Column::DEFAULT.to_vec(),
+            colors: Some(LsColors::default()),
+            max_name_width: None,
+            time_field: TimeField::default(),
+            time_style: TimeStyle::default(),
+            quoting: QuotingStyle::default(),
+            hide_control_chars: true,
+            group_headers: GroupBy::None,
+        }
+    }
+}
+
+/// Writes `entries` to `out` using the layout in `options`.
+pub fn render<W: Write>(out: &mut W, entries: &[Entry], options: &RenderOptions) -> io::Result<()> {
+    match options.layout {
+        Layout::Long => render_long(out, entries, options),
+        Layout::Short => render_grid(out, entries, false, options),
+        Layout::Across => render_grid(out, entries, true, options),
+        Layout::SingleColumn => render_single_column(out, entries, options),
+        _ => write_records(out, entries, options),
+    }
+}
+
+/// Writes each listing under a `path:` header, separated by blank lines,
+/// like `ls -R`.
+///
+/// Machine-readable layouts have no headers and instead write the entries of
+/// every listing as one sequence, relying on each entry's `path`.
+pub fn render_listings<W: Write>(
+    out: &mut W,
+    listings: &[Listing],
+    options: &RenderOptions,
+) -> io::Result<()> {
+    render_operands(out, &[], listings, true, options)
+}
+
+/// Writes the results for several path operands, like `ls a.txt src docs`.
+///
+/// File operands are written first as a single group, followed by each
+/// listing, separated by blank lines and under a `path:` header when
+/// `headers` is set. Machine-readable layouts write the files and the entries
+/// of every listing as one sequence.
+pub fn render_operands<W: Write>(
+    out: &mut W,
+    files: &[Entry],
+    listings: &[Listing],
+    headers: bool,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    if options.layout.is_machine_readable() {
+        let all_entries = files
+            .iter()
+            .chain(listings.iter().flat_map(|listing| &listing.entries));
+        return write_records(out, all_entries, options);
+    }
+
+    if !files.is_empty() {
+        render(out, files, options)?;
+    }
+    for (i, listing) in listings.iter().enumerate() {
+        if i > 0 || !files.is_empty() {
+            writeln!(out)?;
+        }
+        if headers {
+            writeln!(out, "{}:", display_text(listing.path.as_os_str(), options))?;
+        }
+        render(out, &listing.entries, options)?;
+    }
+    Ok(())
+}
+
+/// Writes `nodes` as a tree rooted at `root`, like the `tree` command.
+///
+/// In the long layout each line is prefixed with the entry's size and
+/// modification time. The JSON layout nests each directory's entries in a
+/// `children` array, while the other machine-readable layouts write every
+/// entry in tree order.
+pub fn render_tree<W: Write>(
+    out: &mut W,
+    root: &Path,
+    nodes: &[TreeNode],
+    options: &RenderOptions,
+) -> io::Result<()> {
+    if options.layout == Layout::Json {
+        return json::write_tree(out, nodes);
+    } else if options.layout.is_machine_readable() {
+        let mut entries = Vec::new();
+        flatten_tree(nodes, &mut entries);
+        return write_records(out, entries, options);
+    }
+
+    writeln!(out, "{}", display_text(root.as_os_str(), options))?;
+    render_tree_level(out, nodes, "", options)
+}
+
+/// Writes a tree for each of several path operands, one after the other.
+///
+/// Each root's entry is the operand itself, named as it was given, and its
+/// children are the directory's contents. File operands are roots without
+/// children. The JSON layout writes the roots as an array of nested entries.
+pub fn render_trees<W: Write>(
+    out: &mut W,
+    roots: &[TreeNode],
+    options: &RenderOptions,
+) -> io::Result<()> {
+    if options.layout == Layout::Json {
+        return json::write_tree(out, roots);
+    } else if options.layout.is_machine_readable() {
+        let mut entries = Vec::new();
+        flatten_tree(roots, &mut entries);
+        return write_records(out, entries, options);
+    }
+
+    for root in roots {
+        writeln!(out, "{}", name_with_target(&root.entry, options))?;
+        render_tree_level(out, &root.children, "", options)?;
+    }
+    Ok(())
+}
+
+/// Writes entries in one of the machine-readable layouts.
+fn write_records<'a, W, I>(out: &mut W, entries: I, options: &RenderOptions) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Entry>,
+{
+    match options.layout {
+        Layout::Json => json::write_array(out, entries),
+        Layout::Ndjson => json::write_lines(out, entries),
+        Layout::Csv => csv::write_csv(out, entries, &options.columns, options.hide_control_chars),
+        Layout::Tsv => csv::write_tsv(out, entries, &options.columns, options.hide_control_chars),
+        Layout::Long | Layout::Short | Layout::Across | Layout::SingleColumn => {
+            unreachable!("{:?} is not a record layout", options.layout)
+        }
+    }
+}
+
+fn flatten_tree<'a>(nodes: &'a [TreeNode], entries: &mut Vec<&'a Entry>) {
+    for node in nodes {
+        entries.push(&node.entry);
+        flatten_tree(&node.children, entries);
+    }
+}
+
+fn render_tree_level<W: Write>(
+    out: &mut W,
+    nodes: &[TreeNode],
+    prefix: &str,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    for (i, node) in nodes.iter().enumerate() {
+        let entry = &node.entry;
+        let last = i + 1 == nodes.len();
+        let connector = if last { "└── " } else { "├── " };
+
+        if options.layout == Layout::Long {
+            let size_str = format_size(entry.size, options.human);
+            let time_str = options
+                .time_style
+                .format(entry.time(options.time_field), SystemTime::now());
+            write!(out, "{:>8} {}  ", size_str, time_str)?;
+        }
+        writeln!(
+            out,
+            "{}{}{}",
+            prefix,
+            connector,
+            name_with_target(entry, options)
+        )?;
+
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_tree_level(out, &node.children, &child_prefix, options)?;
+    }
+    Ok(())
+}
+
+fn render_long<W: Write>(
+    out: &mut W,
+    entries: &[Entry],
+    options: &RenderOptions,
+) -> io::Result<()> {
+    let owners: Vec<(String, String)> = entries
+        .iter()
+        .map(|entry| {
+            if options.numeric_ids {
+                (entry.uid.to_string(), entry.gid.to_string())
+            } else {
+                (user_name(entry.uid), group_name(entry.gid))
+            }
+        })
+        .collect();
+    let link_width = entries
+        .iter()
+        .map(|entry| entry.nlink.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let user_width = owners
+        .iter()
+        .map(|(user, _)| display_width(user))
+        .max()
+        .unwrap_or(0);
+    let group_width = owners
+        .iter()
+        .map(|(_, group)| display_width(group))
+        .max()
+        .unwrap_or(0);
+    let now = SystemTime::now();
+    let times: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            options
+                .time_style
+                .format(entry.time(options.time_field), now)
+        })
+        .collect();
+    let time_width = times
+        .iter()
+        .map(|time| display_width(time))
+        .max()
+        .unwrap_or(0);
+
+    let mut current_group = None;
+    for ((entry, (user, group)), time_str) in entries.iter().zip(&owners).zip(&times) {
+        if let Some(label) = options.group_headers.label(entry) {
+            if current_group.as_ref() != Some(&label) {
+                if current_group.is_some() {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{}:", label)?;
+                current_group = Some(label);
+            }
+        }
+
+        let size_str = format_size(entry.size, options.human);
+        let name = name_with_target(entry, options);
+
+        // Pad names by hand, as format widths count chars rather than columns
+        writeln!(
+            out,
+            "{} {:>link_width$} {}{:user_padding$} {}{:group_padding$} {:>8} {}{:time_padding$} {}",
+            format_mode(entry.mode),
+            entry.nlink,
+            user,
+            "",
+            group,
+            "",
+            size_str,
+            time_str,
+            "",
+            name,
+            user_padding = user_width - display_width(user),
+            group_padding = group_width - display_width(group),
+            time_padding = time_width - display_width(time_str),
+        )?;
+    }
+    Ok(())
+}
+
+/// Spaces between grid columns.
+const COLUMN_GAP: usize = 2;
+
+/// Writes names in the fewest rows that fit `term_width`, like `ls -C`, or
+/// `ls -x` when `across` is set.
+fn render_grid<W: Write>(
+    out: &mut W,
+    entries: &[Entry],
+    across: bool,
+    options: &RenderOptions,
+) -> io::Result<()> {
+    let widths: Vec<usize> = entries
+        .iter()
+        .map(|entry| short_name_width(entry, options))
+        .collect();
+    let (rows, column_widths) = grid_shape(&widths, options.term_width, across);
+    let columns = column_widths.len();
+
+    for row in 0..rows {
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let (index, next) = if across {
+                let index = row * columns + column;
+                (index, (column + 1 < columns).then_some(index + 1))
+            } else {
+                let index = column * rows + row;
+                (index, Some(index + rows))
+            };
+            let Some(entry) = entries.get(index) else {
+                break;
+            };
+
+            write!(out, "{}", short_name(entry, options))?;
+            if next.is_some_and(|next| next < entries.len()) {
+                // Pad by hand, as escape sequences would count towards a format width
+                let padding = column_width - widths[index] + COLUMN_GAP;
+                write!(out, "{:padding$}", "")?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Finds the number of rows and the width of each column for the grid with
+/// the most columns whose lines fit in `max_width`.
+///
+/// A single column is used when even that doesn't fit.
+fn grid_shape(widths: &[usize], max_width: usize, across: bool) -> (usize, Vec<usize>) {
+    let count = widths.len();
+    if count == 0 {
+        return (0, Vec::new());
+    }
+
+    // Every column takes at least one character plus the gap
+    let max_columns = ((max_width + COLUMN_GAP) / (1 + COLUMN_GAP)).clamp(1, count);
+    for columns in (1..=max_columns).rev() {
+        let rows = count.div_ceil(columns);
+        // Filling down columns may leave trailing columns empty
+        let columns = if across {
+            columns
+        } else {
+            count.div_ceil(rows)
+        };
+
+        let mut column_widths = vec![0; columns];
+        for (i, &width) in widths.iter().enumerate() {
+            let column = if across { i % columns } else { i / rows };
+            column_widths[column] = column_widths[column].max(width);
+        }
+        let line_width = column_widths.iter().sum::<usize>() + COLUMN_GAP * (columns - 1);
+        if line_width <= max_width || columns == 1 {
+            return (rows, column_widths);
+        }
+    }
+    unreachable!("a single column always fits")
+}
+
+fn render_single_column<W: Write>(
+    out: &mut W,
+    entries: &[Entry],
+    options: &RenderOptions,
+) -> io::Result<()> {
+    for entry in entries {
+        writeln!(out, "{}", short_name(entry, options))?;
+    }
+    Ok(())
+}
+
+/// The colored name, with a trailing `/` for directories.
+fn short_name(entry: &Entry, options: &RenderOptions) -> String {
+    let name = display_name(entry, options);
+    if entry.is_dir {
+        paint(entry, &format!("{}/", name), options)
+    } else {
+        paint(entry, &name, options)
+    }
+}
+
+/// The width of [`short_name`] on screen.
+fn short_name_width(entry: &Entry, options: &RenderOptions) -> usize {
+    display_width(&display_name(entry, options)) + if entry.is_dir { 1 } else { 0 }
+}
+
+/// The entry's name as displayed, truncated to `max_name_width` if one is set.
+fn display_name(entry: &Entry, options: &RenderOptions) -> String {
+    let name = display_text(&entry.name, options);
+    match options.max_name_width {
+        Some(max_width) => truncate(&name, max_width).into_owned(),
+        None => name,
+    }
+}
+
+/// The colored name followed by ` -> target` for symlinks.
+fn name_with_target(entry: &Entry, options: &RenderOptions) -> String {
+    let name = paint(entry, &display_name(entry, options), options);
+    match &entry.link_target {
+        Some(target) => format!("{} -> {}", name, display_text(target.as_os_str(), options)),
+        None => name,
+    }
+}
+
+/// Quotes a name or path, hiding control characters if asked to.
+fn display_text(text: &OsStr, options: &RenderOptions) -> String {
+    let text = quote(text, options.quoting);
+    if options.hide_control_chars {
+        hide_control_chars(&text).into_owned()
+    } else {
+        text
+    }
+}
+
+fn paint(entry: &Entry, text: &str, options: &RenderOptions) -> String {
+    match &options.colors {
+        Some(colors) => colors.paint(entry, text),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(name: &str) -> Entry {
+        Entry::test_file(name)
+    }
+
+    #[test]
+    fn test_render_tree_connectors() {
+        let nodes = vec![
+            TreeNode {
+                entry: file("a"),
+                children: vec![
+                    TreeNode {
+                        entry: file("b"),
+                        children: Vec::new(),
+                    },
+                    TreeNode {
+                        entry: file("c"),
+                        children: Vec::new(),
+                    },
+                ],
+            },
+            TreeNode {
+                entry: file("d"),
+                children: Vec::new(),
+            },
+        ];
+        let options = RenderOptions {
+            layout: Layout::Short,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render_tree(&mut out, Path::new("root"), &nodes, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "root\n├── a\n│   ├── b\n│   └── c\n└── d\n"
+        );
+    }
+
+    #[test]
+    fn test_render_tree_long_prefix() {
+        let nodes = vec![TreeNode {
+            entry: file("a"),
+            children: Vec::new(),
+        }];
+
+        let mut out = Vec::new();
+        render_tree(&mut out, Path::new("."), &nodes, &RenderOptions::default()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), ".\n      0B ???  └── a\n");
+    }
+
+    #[test]
+    fn test_render_operands_files_first() {
+        let listings = vec![
+            Listing {
+                path: PathBuf::from("src"),
+                entries: vec![file("main.rs")],
+                errors: Vec::new(),
+            },
+            Listing {
+                path: PathBuf::from("docs"),
+                entries: Vec::new(),
+                errors: Vec::new(),
+            },
+        ];
+        let options = RenderOptions {
+            layout: Layout::Short,
+            colors: None,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render_operands(&mut out, &[file("Cargo.toml")], &listings, true, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Cargo.toml\n\nsrc:\nmain.rs\n\ndocs:\n"
+        );
+
+        let mut out = Vec::new();
+        render_operands(&mut out, &[], &listings[..1], false, &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "main.rs\n");
+    }
+
+    fn grid(layout: Layout, names: &[&str], term_width: usize) -> String {
+        let entries: Vec<Entry> = names.iter().map(|name| file(name)).collect();
+        let options = RenderOptions {
+            layout,
+            term_width,
+            colors: None,
+            ..RenderOptions::default()
+        };
+        let mut out = Vec::new();
+        render(&mut out, &entries, &options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render_grid_down_columns() {
+        let names = ["a", "bbbbbb", "c", "dd", "e", "ffff", "g"];
+        assert_eq!(
+            grid(Layout::Short, &names, 20),
+            "a       c   e     g\n\
+             bbbbbb  dd  ffff\n"
+        );
+        assert_eq!(
+            grid(Layout::Short, &names, 14),
+            "a       e\n\
+             bbbbbb  ffff\n\
+             c       g\n\
+             dd\n"
+        );
+        assert_eq!(
+            grid(Layout::Short, &names, 80),
+            "a  bbbbbb  c  dd  e  ffff  g\n"
+        );
+    }
+
+    #[test]
+    fn test_render_grid_across_rows() {
+        let names = ["a", "bbbbbb", "c", "dd", "e", "ffff", "g"];
+        assert_eq!(
+            grid(Layout::Across, &names, 20),
+            "a  bbbbbb  c  dd\n\
+             e  ffff    g\n"
+        );
+    }
+
+    #[test]
+    fn test_render_grid_narrow_terminal() {
+        // Names wider than the terminal fall back to one per line
+        assert_eq!(
+            grid(Layout::Short, &["long_name", "x"], 4),
+            "long_name\nx\n"
+        );
+        assert_eq!(grid(Layout::Short, &[], 80), "");
+        assert_eq!(grid(Layout::SingleColumn, &["a", "b"], 80), "a\nb\n");
+    }
+
+    #[test]
+    fn test_render_grid_wide_names() {
+        // Each CJK character takes two columns
+        assert_eq!(
+            grid(Layout::Short, &["日本語", "a", "b", "c"], 10),
+            "日本語  b\n\
+             a       c\n"
+        );
+    }
+
+    #[test]
+    fn test_render_truncates_long_names() {
+        let dir = Entry::test_dir("documents");
+        let options = RenderOptions {
+            layout: Layout::Short,
+            colors: None,
+            max_name_width: Some(5),
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render(
+            &mut out,
+            &[dir, file("a.txt"), file("日本語.txt")],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "docu…/  a.txt  日本…\n");
+    }
+
+    #[test]
+    fn test_render_quotes_names_and_targets() {
+        let mut link = file("my link");
+        link.mode = 0o120777;
+        link.link_target = Some(PathBuf::from("a\nb"));
+        let options = RenderOptions {
+            layout: Layout::Short,
+            colors: None,
+            quoting: QuotingStyle::ShellEscape,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render_tree(
+            &mut out,
+            Path::new("my dir"),
+            &[TreeNode {
+                entry: link,
+                children: Vec::new(),
+            }],
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "'my dir'\n└── 'my link' -> 'a'$'\\n''b'\n"
+        );
+    }
+
+    #[test]
+    fn test_render_hides_control_chars() {
+        let mut link = file("evil\x1b]0;pwned\x07\nname");
+        link.mode = 0o120777;
+        link.link_target = Some(PathBuf::from("\x1b[2J"));
+        let listings = vec![Listing {
+            path: PathBuf::from("dir\r"),
+            entries: vec![link],
+            errors: Vec::new(),
+        }];
+
+        for layout in [
+            Layout::Long,
+            Layout::Short,
+            Layout::Across,
+            Layout::SingleColumn,
+        ] {
+            let options = RenderOptions {
+                layout,
+                numeric_ids: true,
+                colors: None,
+                ..RenderOptions::default()
+            };
+            let mut out = Vec::new();
+            render_listings(&mut out, &listings, &options).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            assert!(!out.contains(['\x1b', '\x07', '\r']), "{:?}", layout);
+            assert!(out.starts_with("dir?:\n"));
+            assert!(out.contains("evil?]0;pwned??name"));
+        }
+
+        let options = RenderOptions {
+            layout: Layout::SingleColumn,
+            colors: None,
+            hide_control_chars: false,
+            ..RenderOptions::default()
+        };
+        let mut out = Vec::new();
+        render(&mut out, &listings[0].entries, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "evil\x1b]0;pwned\x07\nname\n"
+        );
+    }
+
+    #[test]
+    fn test_render_without_colors() {
+        let dir = Entry::test_dir("src");
+        let options = RenderOptions {
+            layout: Layout::Short,
+            colors: None,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render(&mut out, &[dir.clone(), file("main.rs")], &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "src/  main.rs\n");
+
+        let mut out = Vec::new();
+        let options = RenderOptions::default();
+        render(&mut out, &[dir], &options).unwrap();
+        assert!(out.contains(&0x1b));
+    }
+
+    #[test]
+    fn test_render_long_shows_link_target() {
+        let mut link = file("link");
+        link.mode = 0o120777;
+        link.link_target = Some(PathBuf::from("a"));
+        let options = RenderOptions {
+            numeric_ids: true,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render(&mut out, &[link], &options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("lrwxrwxrwx"));
+        assert!(out.ends_with(" -> a\n"));
+    }
+
+    #[test]
+    fn test_render_long_aligns_owner_columns() {
+        let mut wide = file("wide");
+        wide.nlink = 12;
+        wide.uid = 1000;
+        wide.gid = 100;
+        let entries = vec![file("a"), wide];
+        let options = RenderOptions {
+            numeric_ids: true,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render(&mut out, &entries, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "-rw-r--r--  1 0    0         0B ??? a\n\
+             -rw-r--r-- 12 1000 100       0B ??? wide\n"
+        );
+    }
+
+    #[test]
+    fn test_render_long_group_headers() {
+        let dir = Entry::test_dir("src");
+        let entries = vec![dir, file("Cargo.toml")];
+        let options = RenderOptions {
+            numeric_ids: true,
+            colors: None,
+            group_headers: GroupBy::Type,
+            ..RenderOptions::default()
+        };
+
+        let mut out = Vec::new();
+        render(&mut out, &entries, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Directories:\n\
+             drwxr-xr-x 2 0 0     4.0K ??? src\n\
+             \n\
+             Files:\n\
+             -rw-r--r-- 1 0 0       0B ??? Cargo.toml\n"
+        );
+    }
+}
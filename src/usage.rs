@@ -0,0 +1,349 @@
+//! Recursive directory sizes, like `du`.
+
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::{Add, Sub},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// The space taken up by a directory and everything below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// The sum of the sizes, as `du --apparent-size` counts them.
+    pub size: u64,
+    /// The space allocated on disk, in 512-byte blocks.
+    pub blocks: u64,
+}
+
+impl Add for DiskUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        DiskUsage {
+            size: self.size + other.size,
+            blocks: self.blocks + other.blocks,
+        }
+    }
+}
+
+impl Sub for DiskUsage {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        DiskUsage {
+            size: self.size - other.size,
+            blocks: self.blocks - other.blocks,
+        }
+    }
+}
+
+/// Totals the directory at `path`, following it if it's a symlink, and
+/// everything below it, reading sibling subtrees in parallel.
+///
+/// Files with several hard links are counted once. Symlinks below `path`
+/// aren't followed, and directories on other filesystems are skipped unless
+/// `cross_filesystems` is set. Entries that can't be read are left out, so
+/// the total is a lower bound when some of the tree is unreadable.
+pub fn disk_usage(path: &Path, cross_filesystems: bool) -> DiskUsage {
+    let device = fs::metadata(path).ok().map(|metadata| device(&metadata));
+    Totals::new(cross_filesystems).total(path, device).usage
+}
+
+/// What's below a directory, along with the hard-linked files in it so
+/// directories sharing them can still count each once.
+#[derive(Debug, Default)]
+struct Subtree {
+    usage: DiskUsage,
+    /// The usage of each file with other hard links, by (device, inode).
+    links: HashMap<(u64, u64), DiskUsage>,
+}
+
+impl Subtree {
+    /// Combines subtrees, counting a file hard-linked into several once.
+    fn merge(subtrees: impl IntoIterator<Item = Self>) -> Self {
+        let mut merged = Subtree::default();
+        for subtree in subtrees {
+            merged.usage = merged.usage + subtree.usage;
+            for (id, usage) in subtree.links {
+                if merged.links.insert(id, usage).is_some() {
+                    merged.usage = merged.usage - usage;
+                }
+            }
+        }
+        merged
+    }
+}
+
+/// Directory totals for one listing, which may include the same directories
+/// several times when it's recursive. The contents of each directory are
+/// only walked once and remembered for the next time they're needed.
+#[derive(Debug, Default)]
+pub(crate) struct Totals {
+    cross_filesystems: bool,
+    contents: Mutex<HashMap<ContentsKey, Arc<Subtree>>>,
+}
+
+/// Identifies what's below a directory by its path and the filesystem the
+/// walk stayed on.
+type ContentsKey = (PathBuf, Option<u64>);
+
+impl Totals {
+    pub(crate) fn new(cross_filesystems: bool) -> Self {
+        Totals {
+            cross_filesystems,
+            contents: Mutex::default(),
+        }
+    }
+
+    /// Totals the directories at `paths`, following symlinks, staying on the
+    /// filesystem `boundary` is on.
+    ///
+    /// The directories are walked in parallel, but a file hard-linked into
+    /// several of them always counts toward the first, as if they had been
+    /// walked in order.
+    pub(crate) fn siblings(&self, boundary: &Path, paths: &[&Path]) -> Vec<DiskUsage> {
+        let device = fs::metadata(boundary)
+            .ok()
+            .map(|metadata| device(&metadata));
+        let subtrees: Vec<Subtree> = paths
+            .par_iter()
+            .map(|path| self.total(path, device))
+            .collect();
+        let mut seen = HashSet::new();
+        subtrees
+            .into_iter()
+            .map(|subtree| {
+                subtree
+                    .links
+                    .into_iter()
+                    .filter(|(id, _)| !seen.insert(*id))
+                    .fold(subtree.usage, |total, (_, usage)| total - usage)
+            })
+            .collect()
+    }
+
+    /// Totals the directory at `path`, following it if it's a symlink. A
+    /// directory on another filesystem only counts itself.
+    fn total(&self, path: &Path, device: Option<u64>) -> Subtree {
+        let Ok(metadata) = fs::metadata(path) else {
+            return Subtree::default();
+        };
+        let own = usage(&metadata);
+        if self.crosses(&metadata, device) {
+            return Subtree {
+                usage: own,
+                links: HashMap::new(),
+            };
+        }
+        let contents = self.contents(path, device);
+        Subtree {
+            usage: own + contents.usage,
+            links: contents.links.clone(),
+        }
+    }
+
+    fn crosses(&self, metadata: &fs::Metadata, device: Option<u64>) -> bool {
+        !self.cross_filesystems && device.is_some_and(|dev| self::device(metadata) != dev)
+    }
+
+    fn contents(&self, path: &Path, device: Option<u64>) -> Arc<Subtree> {
+        let key = (path.to_path_buf(), device);
+        if let Some(contents) = self.contents.lock().unwrap().get(&key) {
+            return Arc::clone(contents);
+        }
+        let paths: Vec<PathBuf> = match fs::read_dir(path) {
+            Ok(dir_entries) => dir_entries.flatten().map(|entry| entry.path()).collect(),
+            Err(_) => Vec::new(),
+        };
+        let subtrees: Vec<Subtree> = paths
+            .par_iter()
+            .map(|path| self.entry(path, device))
+            .collect();
+        let contents = Arc::new(Subtree::merge(subtrees));
+        self.contents
+            .lock()
+            .unwrap()
+            .insert(key, Arc::clone(&contents));
+        contents
+    }
+
+    fn entry(&self, path: &Path, device: Option<u64>) -> Subtree {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return Subtree::default();
+        };
+        let own = usage(&metadata);
+        if metadata.is_dir() {
+            if self.crosses(&metadata, device) {
+                return Subtree::default();
+            }
+            let contents = self.contents(path, device);
+            return Subtree {
+                usage: own + contents.usage,
+                links: contents.links.clone(),
+            };
+        }
+        let links = hard_link_id(&metadata)
+            .map(|id| HashMap::from([(id, own)]))
+            .unwrap_or_default();
+        Subtree { usage: own, links }
+    }
+}
+
+#[cfg(unix)]
+fn usage(metadata: &fs::Metadata) -> DiskUsage {
+    use std::os::unix::fs::MetadataExt;
+    DiskUsage {
+        size: metadata.len(),
+        blocks: metadata.blocks(),
+    }
+}
+
+#[cfg(not(unix))]
+fn usage(metadata: &fs::Metadata) -> DiskUsage {
+    DiskUsage {
+        size: metadata.len(),
+        blocks: metadata.len().div_ceil(512),
+    }
+}
+
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::dev(metadata)
+}
+
+/// Other platforms don't expose the device, so every directory is treated as
+/// being on the same filesystem.
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Identifies a file that has other hard links, and so might be seen again.
+#[cfg(unix)]
+fn hard_link_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hard_link_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_disk_usage_totals_subtree() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("one"), vec![0; 1000]).unwrap();
+        fs::write(root.join("a/two"), vec![0; 2000]).unwrap();
+        fs::write(root.join("a/b/three"), vec![0; 3000]).unwrap();
+
+        let dirs: u64 = ["", "a", "a/b"]
+            .iter()
+            .map(|dir| fs::metadata(root.join(dir)).unwrap().len())
+            .sum();
+        let usage = disk_usage(root, false);
+        assert_eq!(usage.size, 6000 + dirs);
+        assert_eq!(
+            disk_usage(&root.join("a/b"), false).size,
+            3000 + fs::metadata(root.join("a/b")).unwrap().len()
+        );
+        assert_eq!(
+            disk_usage(&root.join("missing"), false),
+            DiskUsage::default()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_usage_counts_hard_links_once() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("file"), vec![1; 8192]).unwrap();
+        fs::hard_link(root.join("file"), root.join("sub/link")).unwrap();
+        std::os::unix::fs::symlink("file", root.join("symlink")).unwrap();
+
+        let file = fs::metadata(root.join("file")).unwrap();
+        let symlink = fs::symlink_metadata(root.join("symlink")).unwrap();
+        let dirs =
+            fs::metadata(root).unwrap().len() + fs::metadata(root.join("sub")).unwrap().len();
+        let usage = disk_usage(root, false);
+        assert_eq!(usage.size, file.len() + symlink.len() + dirs);
+
+        // Totalled on its own, the subdirectory counts the link too
+        assert!(disk_usage(&root.join("sub"), false).size >= 8192);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_siblings_count_hard_links_once_in_order() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("a")).unwrap();
+        fs::create_dir(root.join("b")).unwrap();
+        fs::write(root.join("a/file"), vec![1; 8192]).unwrap();
+        fs::hard_link(root.join("a/file"), root.join("b/link")).unwrap();
+        let own = fs::metadata(root.join("a")).unwrap().len();
+
+        let totals = Totals::new(false);
+        let (a, b) = (root.join("a"), root.join("b"));
+        let sizes: Vec<u64> = totals
+            .siblings(root, &[&a, &b])
+            .iter()
+            .map(|usage| usage.size)
+            .collect();
+        assert_eq!(sizes, [own + 8192, own]);
+        let sizes: Vec<u64> = totals
+            .siblings(root, &[&b, &a])
+            .iter()
+            .map(|usage| usage.size)
+            .collect();
+        assert_eq!(sizes, [own + 8192, own]);
+    }
+
+    #[test]
+    fn test_totals_walk_each_directory_once() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let (a, b) = (root.join("a"), root.join("a/b"));
+        fs::create_dir_all(&b).unwrap();
+        fs::write(b.join("file"), vec![1; 8192]).unwrap();
+        let own = fs::metadata(&b).unwrap().len();
+
+        let totals = Totals::new(false);
+        totals.siblings(root, &[&a]);
+        // Listing a recursively asks for b again, which was already walked,
+        // so a file added since isn't seen
+        fs::write(b.join("later"), vec![1; 8192]).unwrap();
+        assert_eq!(totals.siblings(&a, &[&b])[0].size, own + 8192);
+        assert_eq!(Totals::new(false).siblings(&a, &[&b])[0].size, own + 16384);
+    }
+
+    #[test]
+    fn test_totals_stop_at_other_filesystems() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("mount")).unwrap();
+        fs::write(root.join("mount/file"), vec![1; 8192]).unwrap();
+        let mount = root.join("mount");
+        let own = fs::metadata(&mount).unwrap().len();
+
+        // Pretend the listed directory is on another filesystem
+        let device = Some(u64::MAX);
+        assert_eq!(Totals::new(false).total(&mount, device).usage.size, own);
+        assert_eq!(
+            Totals::new(true).total(&mount, device).usage.size,
+            own + 8192
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Display widths of text on a terminal.
+//!
+//! Widths are measured per grapheme cluster, so wide CJK characters take two
+//! columns and combining marks or emoji sequences don't add extra ones.
+
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "…";
+
+/// The number of terminal columns `text` takes up.
+pub(crate) fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(UnicodeWidthStr::width).sum()
+}
+
+/// Shortens `text` to at most `max_width` columns, ending it with an ellipsis
+/// when anything was cut.
+///
+/// Grapheme clusters are never split.
+pub(crate) fn truncate(text: &str, max_width: usize) -> Cow<'_, str> {
+    if display_width(text) <= max_width {
+        return Cow::Borrowed(text);
+    }
+
+    let available = max_width.saturating_sub(ELLIPSIS.width());
+    let mut width = 0;
+    let mut end = 0;
+    for (offset, grapheme) in text.grapheme_indices(true) {
+        width += grapheme.width();
+        if width > available {
+            break;
+        }
+        end = offset + grapheme.len();
+    }
+    Cow::Owned(format!("{}{}", &text[..end], ELLIPSIS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("main.rs"), 7);
+        assert_eq!(display_width("日本語.txt"), 10);
+        // e followed by a combining acute accent
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(display_width("👍🏽"), 2);
+        assert_eq!(display_width(""), 0);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("main.rs", 7), "main.rs");
+        assert_eq!(truncate("main.rs", 5), "main…");
+        assert_eq!(truncate("main.rs", 1), "…");
+    }
+
+    #[test]
+    fn test_truncate_keeps_graphemes_whole() {
+        // A wide character that would straddle the limit is dropped whole
+        assert_eq!(truncate("日本語.txt", 6), "日本…");
+        assert_eq!(truncate("cafe\u{301}s", 5), "cafe\u{301}s");
+        assert_eq!(truncate("cafe\u{301}s", 4), "caf…");
+        assert_eq!(truncate("ab👍🏽cd", 4), "ab…");
+    }
+}
@@ -1,11 +1,34 @@
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 use tempfile::tempdir;
 
+mod common;
+
+/// Builds a command running zls with `args` on `dir`.
+fn zls_command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new("cargo");
+    command.args(["run", "--"]).args(args).arg(dir);
+    command
+}
+
+/// Runs zls with `args` on `dir`, expecting success, and returns its output.
+fn zls(dir: &Path, args: &[&str]) -> String {
+    let output = zls_command(dir, args)
+        .output()
+        .expect("Failed to execute zls");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
 #[test]
 fn test_zls_current_directory() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "."])
+        .args(["run", "--", "."])
         .output()
         .expect("Failed to execute zls");
 
@@ -19,7 +42,7 @@ fn test_zls_current_directory() {
 #[test]
 fn test_zls_help() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "--help"])
+        .args(["run", "--", "--help"])
         .output()
         .expect("Failed to execute zls --help");
 
@@ -36,13 +59,15 @@ fn test_zls_help() {
 #[test]
 fn test_zls_nonexistent_directory() {
     let output = Command::new("cargo")
-        .args(&["run", "--", "/nonexistent/directory"])
+        .args(["run", "--", "/nonexistent/directory"])
         .output()
         .expect("Failed to execute zls");
 
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(2));
     let stderr = String::from_utf8_lossy(&output.stderr);
-    assert!(stderr.contains("does not exist"));
+    assert!(
+        stderr.contains("zls: cannot access '/nonexistent/directory': No such file or directory")
+    );
 }
 
 #[test]
@@ -56,7 +81,7 @@ fn test_zls_with_flags() {
 
     // Test without --all flag (should not show hidden files)
     let output = Command::new("cargo")
-        .args(&["run", "--", dir.path().to_str().unwrap()])
+        .args(["run", "--", dir.path().to_str().unwrap()])
         .output()
         .expect("Failed to execute zls");
 
@@ -68,7 +93,7 @@ fn test_zls_with_flags() {
 
     // Test with --all flag (should show hidden files)
     let output = Command::new("cargo")
-        .args(&["run", "--", "--all", dir.path().to_str().unwrap()])
+        .args(["run", "--", "--all", dir.path().to_str().unwrap()])
         .output()
         .expect("Failed to execute zls --all");
 
@@ -80,7 +105,7 @@ fn test_zls_with_flags() {
 
     // Test with --short flag (original behavior is now default, so test short)
     let output = Command::new("cargo")
-        .args(&["run", "--", "--short", dir.path().to_str().unwrap()])
+        .args(["run", "--", "--short", dir.path().to_str().unwrap()])
         .output()
         .expect("Failed to execute zls --short");
 
@@ -92,7 +117,7 @@ fn test_zls_with_flags() {
 
     // Test default behavior (now long format)
     let output = Command::new("cargo")
-        .args(&["run", "--", dir.path().to_str().unwrap()])
+        .args(["run", "--", dir.path().to_str().unwrap()])
         .output()
         .expect("Failed to execute zls (default)");
 
@@ -114,7 +139,7 @@ fn test_zls_human_readable_sizes() {
         .expect("Failed to create large file");
 
     let output = Command::new("cargo")
-        .args(&["run", "--", "-H", dir.path().to_str().unwrap()])
+        .args(["run", "--", "-H", dir.path().to_str().unwrap()])
         .output()
         .expect("Failed to execute zls -H");
 
@@ -126,3 +151,555 @@ fn test_zls_human_readable_sizes() {
     // Should contain size unit indicators
     assert!(stdout.contains("K") || stdout.contains("B"));
 }
+
+#[test]
+fn test_zls_recursive() {
+    let dir = tempdir().expect("Failed to create temp dir");
+
+    fs::create_dir_all(dir.path().join("a/nested")).expect("Failed to create dirs");
+    fs::create_dir(dir.path().join("b")).expect("Failed to create dir");
+    fs::write(dir.path().join("a/nested/deep.txt"), "x").expect("Failed to create file");
+    fs::write(dir.path().join("b/other.txt"), "x").expect("Failed to create file");
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-R", "--short", dir.path().to_str().unwrap()])
+        .output()
+        .expect("Failed to execute zls -R");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let root = format!("{}:", dir.path().display());
+    let a = format!("{}:", dir.path().join("a").display());
+    let nested = format!("{}:", dir.path().join("a/nested").display());
+    let b = format!("{}:", dir.path().join("b").display());
+
+    // Headers should appear in depth-first order
+    let positions: Vec<usize> = [&root, &a, &nested, &b]
+        .iter()
+        .map(|header| stdout.find(header.as_str()).expect("Missing header"))
+        .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    assert!(stdout.contains("deep.txt"));
+    assert!(stdout.contains("other.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_zls_reports_unreadable_entries() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if common::running_as_root() {
+        return;
+    }
+    let dir = tempdir().expect("Failed to create temp dir");
+    let locked = dir.path().join("locked");
+    fs::create_dir(&locked).expect("Failed to create dir");
+    fs::write(dir.path().join("visible.txt"), "x").expect("Failed to create file");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))
+        .expect("Failed to set permissions");
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "-R", dir.path().to_str().unwrap()])
+        .output()
+        .expect("Failed to execute zls -R");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))
+        .expect("Failed to restore permissions");
+
+    // The readable parts are still listed, with a minor-problem exit status
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stdout.contains("visible.txt"));
+    assert!(stderr.contains("zls: cannot open directory"));
+    assert!(stderr.contains("Permission denied"));
+}
+
+#[test]
+fn test_zls_json_output() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(dir.path().join("data.txt"), "12345").expect("Failed to create file");
+    fs::create_dir(dir.path().join("sub")).expect("Failed to create dir");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--format",
+            "json",
+            dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute zls --format json");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Never colored, with raw sizes and types
+    assert!(!stdout.contains('\x1b'));
+    assert!(stdout.starts_with('['));
+    assert!(stdout.contains(r#""name":"data.txt""#));
+    assert!(stdout.contains(r#""size":5"#));
+    assert!(stdout.contains(r#""type":"directory""#));
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--format",
+            "ndjson",
+            dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute zls --format ndjson");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines
+        .iter()
+        .all(|line| line.starts_with('{') && line.ends_with('}')));
+}
+
+#[test]
+fn test_zls_csv_output() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(dir.path().join("a,b.txt"), "123").expect("Failed to create file");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--format",
+            "csv",
+            "--columns",
+            "name,size,type",
+            dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute zls --format csv");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "name,size,type\r\n\"a,b.txt\",3,file\r\n");
+}
+
+#[test]
+fn test_zls_color_control() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::create_dir(dir.path().join("subdir")).expect("Failed to create dir");
+
+    let colored = |args: &[&str], envs: &[(&str, &str)]| {
+        let output = zls_command(dir.path(), args)
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR_FORCE")
+            .envs(envs.iter().copied())
+            .output()
+            .expect("Failed to execute zls");
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).contains('\x1b')
+    };
+
+    // Output is piped here, so auto mode shouldn't color
+    assert!(!colored(&[], &[]));
+    assert!(colored(&["--color=always"], &[]));
+    assert!(colored(&["--color"], &[]));
+    assert!(!colored(&["--color=never"], &[("CLICOLOR_FORCE", "1")]));
+    assert!(colored(&[], &[("CLICOLOR_FORCE", "1")]));
+    assert!(!colored(&[], &[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]));
+    assert!(colored(&["--color=always"], &[("NO_COLOR", "1")]));
+}
+
+#[test]
+fn test_zls_multiple_operands() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(dir.path().join("b.txt"), "b").expect("Failed to create file");
+    fs::write(dir.path().join("a.txt"), "a").expect("Failed to create file");
+    fs::create_dir(dir.path().join("zdir")).expect("Failed to create dir");
+    fs::create_dir(dir.path().join("adir")).expect("Failed to create dir");
+    fs::write(dir.path().join("zdir/inner.txt"), "").expect("Failed to create file");
+
+    // Run from inside the directory so the operands are relative
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let output = Command::new("cargo")
+        .args(["run", "--manifest-path", manifest, "--"])
+        .args(["-s", "--color=never"])
+        .args(["zdir", "b.txt", "missing", "adir", "a.txt"])
+        .current_dir(dir.path())
+        .output()
+        .expect("Failed to execute zls");
+
+    // A missing operand is reported without stopping the others
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("zls: cannot access 'missing': No such file or directory"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "a.txt  b.txt\n\nadir:\n\nzdir:\ninner.txt\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_zls_hostile_names() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    fs::write(dir.path().join("a\x1b]0;title\x07b\nc"), "").expect("Failed to create file");
+
+    // Piped output is left alone unless asked otherwise, like `ls`
+    assert_eq!(zls(dir.path(), &["-1"]), "a\x1b]0;title\x07b\nc\n");
+    assert_eq!(zls(dir.path(), &["-1", "-q"]), "a?]0;title?b?c\n");
+    assert_eq!(
+        zls(dir.path(), &["-1", "-q", "--show-control-chars"]),
+        "a\x1b]0;title\x07b\nc\n"
+    );
+    assert_eq!(
+        zls(dir.path(), &["-1", "--quoting-style=shell-escape"]),
+        "'a'$'\\033'']0;title'$'\\a''b'$'\\n''c'\n"
+    );
+    assert!(zls(dir.path(), &["-q", "--format=csv", "--columns=name"])
+        .ends_with("\r\na?]0;title?b?c\r\n"));
+    let ndjson = zls(dir.path(), &["--format=ndjson"]);
+    assert!(ndjson.contains(r#""name":"a\u001b]0;title\u0007b\nc""#));
+}
+
+#[test]
+fn test_zls_time_selection() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempdir().expect("Failed to create temp dir");
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    let new = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    for (name, modified, accessed) in [("a", old, new), ("b", new, old)] {
+        let file = fs::File::create(dir.path().join(name)).expect("Failed to create file");
+        let times = fs::FileTimes::new()
+            .set_modified(modified)
+            .set_accessed(accessed);
+        file.set_times(times).expect("Failed to set times");
+    }
+
+    assert_eq!(zls(dir.path(), &["-1", "-t"]), "b\na\n");
+    assert_eq!(zls(dir.path(), &["-1", "-t", "-u"]), "a\nb\n");
+    assert_eq!(zls(dir.path(), &["-1", "-t", "--time=atime"]), "a\nb\n");
+    // The last of -u, -c and --time wins
+    assert_eq!(
+        zls(dir.path(), &["-1", "-t", "-u", "--time=modification"]),
+        "b\na\n"
+    );
+    // --time only picks the time, so without -t the names stay in order,
+    // and the old bare --time sort flag is now missing its value
+    assert_eq!(zls(dir.path(), &["-1", "--time=modification"]), "a\nb\n");
+    let bare = zls_command(dir.path(), &["--time"])
+        .output()
+        .expect("Failed to execute zls");
+    assert_eq!(bare.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&bare.stderr).contains("invalid value"));
+
+    let long = zls(dir.path(), &["-n", "-u", "--time-style=+%Y"]);
+    assert!(long.contains(" 2020 a\n"), "{}", long);
+    assert!(long.contains(" 2001 b\n"), "{}", long);
+}
+
+#[test]
+fn test_zls_sort_options() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    for (name, size) in [
+        ("a.txt", 3),
+        ("b.rs", 10),
+        ("c", 3),
+        ("file10", 1),
+        ("file9", 1),
+    ] {
+        fs::write(dir.path().join(name), "x".repeat(size)).expect("Failed to create file");
+    }
+
+    assert_eq!(
+        zls(dir.path(), &["-1", "-S"]),
+        "b.rs\na.txt\nc\nfile10\nfile9\n"
+    );
+    assert_eq!(
+        zls(dir.path(), &["-1", "--sort=size", "-r"]),
+        "file9\nfile10\nc\na.txt\nb.rs\n"
+    );
+    assert_eq!(
+        zls(dir.path(), &["-1", "-X"]),
+        "c\nfile10\nfile9\nb.rs\na.txt\n"
+    );
+    assert_eq!(
+        zls(dir.path(), &["-1", "-v"]),
+        "a.txt\nb.rs\nc\nfile9\nfile10\n"
+    );
+    // The last sort option wins
+    assert_eq!(
+        zls(dir.path(), &["-1", "-S", "--sort=name"]),
+        "a.txt\nb.rs\nc\nfile10\nfile9\n"
+    );
+}
+
+#[test]
+fn test_zls_grouping() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    for name in ["b.rs", "a.txt", "c", "d.rs"] {
+        fs::write(dir.path().join(name), "").expect("Failed to create file");
+    }
+    fs::create_dir(dir.path().join("sub")).expect("Failed to create dir");
+
+    assert_eq!(
+        zls(dir.path(), &["-1", "--group-directories-first", "-r"]),
+        "sub/\nd.rs\nc\nb.rs\na.txt\n"
+    );
+    assert_eq!(
+        zls(dir.path(), &["-1", "--group-by=extension"]),
+        "c\nsub/\nb.rs\nd.rs\na.txt\n"
+    );
+    // Unsorted listings aren't grouped
+    assert_eq!(
+        zls(dir.path(), &["-1", "--group-directories-first", "-U"])
+            .lines()
+            .count(),
+        5
+    );
+
+    let long = zls(dir.path(), &["--group-by=type", "--group-headers"]);
+    let headers: Vec<&str> = long.lines().filter(|line| line.ends_with(':')).collect();
+    assert_eq!(headers, ["Directories:", "Files:"]);
+}
+
+#[test]
+fn test_zls_git_ignore() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir_all(root.join(".git")).expect("Failed to create dir");
+    fs::create_dir_all(root.join("target/debug")).expect("Failed to create dir");
+    fs::create_dir_all(root.join("src")).expect("Failed to create dir");
+    fs::write(root.join(".gitignore"), "target/\n*.log\n!keep.log\n").expect("Failed to write");
+    fs::write(root.join("src/.gitignore"), "generated.rs\n").expect("Failed to write");
+    for name in [
+        "a.log",
+        "keep.log",
+        "src/main.rs",
+        "src/generated.rs",
+        "src/b.log",
+    ] {
+        fs::write(root.join(name), "").expect("Failed to create file");
+    }
+
+    assert_eq!(zls(root, &["-1"]), "a.log\nkeep.log\nsrc/\ntarget/\n");
+    let listed = zls(root, &["-1", "-R", "--git-ignore"]);
+    assert!(listed.contains("keep.log\nsrc/\n"));
+    assert!(listed.ends_with(":\nmain.rs\n"));
+    assert!(!listed.contains("target"));
+    assert!(!listed.contains("a.log"));
+
+    let patterns = root.join("patterns");
+    fs::write(&patterns, "src\n").expect("Failed to write");
+    let listed = zls(root, &["-1", "--ignore-file", patterns.to_str().unwrap()]);
+    assert_eq!(listed, "a.log\nkeep.log\npatterns\ntarget/\n");
+}
+
+#[test]
+fn test_zls_name_filters() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir_all(root.join("src")).expect("Failed to create dir");
+    fs::create_dir_all(root.join("target")).expect("Failed to create dir");
+    for name in [
+        "Cargo.toml",
+        "README.md",
+        "src/main.rs",
+        "src/LIB.RS",
+        "target/out.rs",
+    ] {
+        fs::write(root.join(name), "").expect("Failed to create file");
+    }
+
+    let listed = zls(
+        root,
+        &[
+            "-1",
+            "--include",
+            "*.rs",
+            "--include",
+            "*.toml",
+            "-I",
+            "target",
+        ],
+    );
+    assert_eq!(listed, "Cargo.toml\n");
+    let listed = zls(
+        root,
+        &["-1", "-R", "--iinclude", "*.rs", "--exclude", "target"],
+    );
+    assert!(listed.ends_with(":\nLIB.RS\nmain.rs\n"));
+    // Directories are descended into but only listed when they match
+    assert!(!listed.contains("\nsrc/\n"));
+    assert!(!listed.contains("out.rs"));
+    let listed = zls(root, &["-1", "-R", "--iregex", "^src/m"]);
+    assert!(listed.contains("src:\nmain.rs\n"));
+    assert!(!listed.contains("LIB.RS"));
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "--regex", "("])
+        .output()
+        .expect("Failed to execute zls");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_zls_type_size_and_age_filters() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = tempdir().expect("Failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir_all(root.join("logs")).expect("Failed to create dir");
+    fs::write(root.join("small.txt"), "x").expect("Failed to create file");
+    fs::write(root.join("new.bin"), vec![0; 3 << 20]).expect("Failed to create file");
+    let old = root.join("logs/old.bin");
+    fs::write(&old, vec![0; 2 << 20]).expect("Failed to create file");
+    let month_ago = SystemTime::now() - Duration::from_secs(30 * 86400);
+    fs::File::options()
+        .write(true)
+        .open(&old)
+        .and_then(|file| file.set_modified(month_ago))
+        .expect("Failed to set times");
+
+    assert_eq!(zls(root, &["-1", "--type", "d"]), "logs/\n");
+    assert_eq!(
+        zls(root, &["-1", "--type=f", "--max-size", "1K"]),
+        "small.txt\n"
+    );
+    assert_eq!(
+        zls(
+            root,
+            &["-1", "-S", "--min-size", "1.5M", "--newer-than", "1w"]
+        ),
+        "new.bin\n"
+    );
+
+    // The tree keeps the directory leading to the only match
+    let tree = zls(
+        root,
+        &["--tree", "-s", "--min-size", "1M", "--older-than", "2w"],
+    );
+    assert!(tree.contains("logs\n"));
+    assert!(tree.ends_with("old.bin\n"));
+    assert!(!tree.contains("new.bin"));
+}
+
+#[test]
+fn test_zls_where_queries() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir_all(root.join("logs")).expect("Failed to create dir");
+    fs::write(root.join("logs/big.log"), vec![0; 2 << 20]).expect("Failed to create file");
+    fs::write(root.join("logs/small.log"), "x").expect("Failed to create file");
+    fs::write(root.join("notes.txt"), vec![0; 2 << 20]).expect("Failed to create file");
+
+    let stdout = zls(
+        root,
+        &["-1", "-R", "--where", r#"size > 1M && ext == "log""#],
+    );
+    assert!(stdout.contains("big.log\n"));
+    assert!(!stdout.contains("small.log"));
+    assert!(!stdout.contains("notes.txt"));
+
+    let listed = zls(
+        root,
+        &[
+            "-1",
+            "--where",
+            r#"type == "d" || name =~ "\.txt$""#,
+            "--where",
+            "mtime > now - 1h",
+        ],
+    );
+    assert_eq!(listed, "logs/\nnotes.txt\n");
+
+    let output = zls_command(root, &["--where", "size > 1M && ext == log"])
+        .output()
+        .expect("Failed to execute zls");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown field 'log'"));
+    assert!(stderr.contains("  size > 1M && ext == log\n                      ^"));
+}
+
+#[test]
+fn test_zls_total_size() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir_all(root.join("big/nested")).expect("Failed to create dir");
+    fs::create_dir(root.join("small")).expect("Failed to create dir");
+    fs::write(root.join("big/nested/data"), vec![0; 3 << 20]).expect("Failed to create file");
+    fs::write(root.join("small/data"), vec![0; 1 << 20]).expect("Failed to create file");
+    fs::write(root.join("file"), vec![0; 2 << 20]).expect("Failed to create file");
+
+    // Without totals directories are smaller than the file
+    assert_eq!(zls(root, &["-1", "-S"]), "file\nbig/\nsmall/\n");
+    assert_eq!(
+        zls(root, &["-1", "-S", "--total-size"]),
+        "big/\nfile\nsmall/\n"
+    );
+    assert_eq!(
+        zls(
+            root,
+            &["-1", "--total-size", "--type", "d", "--min-size", "2M"]
+        ),
+        "big/\n"
+    );
+
+    let csv = zls(
+        root,
+        &[
+            "--total-size",
+            "--format",
+            "csv",
+            "--columns",
+            "name,size,blocks",
+        ],
+    );
+    let big = csv
+        .lines()
+        .find(|line| line.starts_with("big,"))
+        .expect("big is listed");
+    let size: u64 = big.split(',').nth(1).unwrap().parse().unwrap();
+    assert!(size > 3 << 20);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_zls_symlink_to_directory_operand() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let root = dir.path();
+    fs::create_dir(root.join("real")).expect("Failed to create dir");
+    fs::write(root.join("real/inside"), "").expect("Failed to create file");
+    std::os::unix::fs::symlink("real", root.join("link")).expect("Failed to create symlink");
+    let link = root.join("link");
+
+    // The long layout shows the link itself, like `ls -l`
+    let long = zls(&link, &[]);
+    assert!(long.contains("link -> real"));
+    assert!(!long.contains("inside"));
+    // Dereferencing or a short layout lists the directory
+    assert!(zls(&link, &["-L"]).contains("inside"));
+    assert_eq!(zls(&link, &["-1"]), "inside\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_zls_broken_symlink_operand() {
+    let dir = tempdir().expect("Failed to create temp dir");
+    let broken = dir.path().join("broken");
+    std::os::unix::fs::symlink("missing", &broken).expect("Failed to create symlink");
+
+    // Listed as the link itself, like `ls`
+    assert!(zls(&broken, &[]).contains("broken -> missing"));
+    assert!(zls(&broken, &["-1"]).ends_with("broken\n"));
+
+    // Following it fails, like `ls -L`
+    let output = zls_command(&broken, &["-L"])
+        .output()
+        .expect("Failed to execute zls");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("cannot access"));
+    assert!(stderr.contains("No such file or directory"));
+}